@@ -12,6 +12,8 @@ fn main() {
         .with_header("// Jackson Coxson\n// Bindings to plist_ffi")
         .with_language(cbindgen::Language::C)
         .with_sys_include("stdio.h")
+        // Taken and returned as uint32_t, so no function mentions it
+        .include_item("PlistMergeStrategy")
         .generate()
         .expect("Unable to generate bindings")
         .write_to_file("plist.h");
@@ -3,78 +3,69 @@
 use plist::Value;
 use std::ptr::null_mut;
 
-use crate::{NodeType, PlistWrapper, plist_array_iter, plist_t};
+use crate::{NodeType, PlistWrapper, borrow_value, ffi_guard, plist_array_iter, plist_t};
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_get_size(node: plist_t) -> u32 {
-    let node = unsafe { &mut *node }.borrow_self();
-    match node {
-        Value::Array(a) => a.len() as u32,
+    ffi_guard(0, || match unsafe { borrow_value(node) } {
+        Some(Value::Array(a)) => a.len() as u32,
         _ => 0,
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_get_item(node: plist_t, n: u32) -> plist_t {
-    let wrapper = unsafe { &mut *node };
-    let mut node = wrapper.borrow_self();
-    match &mut node {
-        Value::Array(a) => {
-            if n as usize >= a.len() {
-                return null_mut();
-            }
-            let p = a.get_mut(n as usize).unwrap();
-            let p = PlistWrapper {
-                node: NodeType::Child {
-                    node: p as *mut Value,
-                    parent: node as *mut Value,
-                    index: n,
-                    key: None,
-                },
-                children_wrappers: Vec::new(),
-            }
-            .into_ptr();
-            wrapper.children_wrappers.push(p);
-            p
+    ffi_guard(null_mut(), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return null_mut();
+        };
+        match wrapper.borrow_self() {
+            Some(Value::Array(a)) if (n as usize) < a.len() => wrapper.child(n, None),
+            _ => null_mut(),
         }
-        _ => null_mut(),
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_get_item_index(node: plist_t) -> u32 {
-    let node = unsafe { &mut *node };
-    match node.node {
-        NodeType::Node(_) => u32::MAX,
-        NodeType::Child { index, .. } => index,
-        NodeType::Iterator(_) => panic!("you passed an iterator as a node"),
-    }
+    ffi_guard(u32::MAX, || {
+        match unsafe { PlistWrapper::from_handle(node) }.map(|w| &w.node) {
+            Some(NodeType::Child { index, .. }) => *index,
+            _ => u32::MAX,
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
-/// Will assert if n > len or if item is a child
+/// Does nothing if n is out of bounds or if item is a child
 /// Don't move a child
 /// The array owns the item now, don't use it
+/// Handles to the replaced item turn invalid
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_set_item(node: plist_t, item: plist_t, n: u32) {
-    let item = unsafe { Box::from_raw(item) };
-    let item = match item.consume() {
-        Some(i) => i,
-        None => {
-            panic!("You just tried to move a child into an array");
+    ffi_guard((), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        let Some(Value::Array(a)) = wrapper.borrow_self() else {
+            return;
+        };
+        if n as usize >= a.len() {
+            return;
         }
-    };
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Array(a) = node {
-        a[n as usize] = item;
-    }
+        // You can't move a child into an array
+        if let Some(item) = unsafe { PlistWrapper::take_handle(item) } {
+            a[n as usize] = item;
+            wrapper.release_children(|index, key| key.is_none() && index == n);
+        }
+    })
 }
 
 /// # Safety
@@ -83,62 +74,80 @@ pub unsafe extern "C" fn plist_array_set_item(node: plist_t, item: plist_t, n: u
 /// The array owns the item now, don't use it
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_append_item(node: plist_t, item: plist_t) {
-    let item = unsafe { Box::from_raw(item) };
-    let item = match item.consume() {
-        Some(i) => i,
-        None => {
-            panic!("You just tried to move a child into an array");
+    ffi_guard((), || {
+        let Some(Value::Array(a)) = (unsafe { borrow_value(node) }) else {
+            return;
+        };
+        // You can't move a child into an array
+        if let Some(item) = unsafe { PlistWrapper::take_handle(item) } {
+            a.push(item);
         }
-    };
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Array(a) = node {
-        a.push(item);
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
-/// Will assert if n > len or if item is a child
+/// Does nothing if n > len or if item is a child
 /// Don't move a child
 /// The array owns the item now, don't use it
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_insert_item(node: plist_t, item: plist_t, n: u32) {
-    let item = unsafe { Box::from_raw(item) };
-    let item = match item.consume() {
-        Some(i) => i,
-        None => {
-            panic!("You just tried to move a child into an array");
+    ffi_guard((), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        let Some(Value::Array(a)) = wrapper.borrow_self() else {
+            return;
+        };
+        if n as usize > a.len() {
+            return;
+        }
+        // You can't move a child into an array
+        if let Some(item) = unsafe { PlistWrapper::take_handle(item) } {
+            a.insert(n as usize, item);
+            wrapper.reindex_children(n, true);
         }
-    };
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Array(a) = node {
-        a.insert(n as usize, item);
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
-/// Will assert if n > len
+/// Does nothing if n >= len
+/// Handles to the removed item turn invalid
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_remove_item(node: plist_t, n: u32) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Array(a) = node {
-        let _ = a.remove(n as usize);
-    }
+    ffi_guard((), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        if let Some(Value::Array(a)) = wrapper.borrow_self()
+            && (n as usize) < a.len()
+        {
+            let _ = a.remove(n as usize);
+            wrapper.reindex_children(n, false);
+        }
+    })
 }
 
 /// Remove self from the parent array
+/// The handle is freed along with the item, don't use it afterwards
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_item_remove(node: plist_t) {
-    let node = unsafe { &mut *node };
-    if let NodeType::Child { parent, index, .. } = &mut node.node {
-        let parent = unsafe { &mut **parent };
-        if let Value::Array(parent) = parent {
-            parent.remove(*index as usize);
+    ffi_guard((), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        if let NodeType::Child {
+            parent,
+            index,
+            key: None,
+        } = wrapper.node
+        {
+            unsafe { plist_array_remove_item(parent as plist_t, index) };
         }
-    }
+    })
 }
 
 /// Since the free function accepts an iterator, the root objects
@@ -147,8 +156,22 @@ pub unsafe extern "C" fn plist_array_item_remove(node: plist_t) {
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_array_new_iter(_node: plist_t, iter: *mut plist_array_iter) {
-    let p = PlistWrapper::new_iterator(0).into_ptr();
-    unsafe { *iter = p };
+    ffi_guard((), || {
+        if iter.is_null() {
+            return;
+        }
+        let p = PlistWrapper::new_iterator(0).into_ptr();
+        unsafe { *iter = p };
+    })
+}
+
+/// Frees an iterator from plist_array_new_iter.
+/// Passing a node is reported through plist_get_last_error and does nothing.
+/// # Safety
+/// Don't pass a bad iterator >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_array_free_iter(iter: plist_array_iter) {
+    ffi_guard((), || unsafe { PlistWrapper::free_iterator(iter) })
 }
 
 /// # Safety
@@ -159,28 +182,25 @@ pub unsafe extern "C" fn plist_array_next_item(
     iter: plist_array_iter,
     item: *mut plist_t,
 ) {
-    let wrapper = unsafe { &mut *node };
-    let node = wrapper.borrow_self();
-
-    if let Value::Array(a) = node {
-        let iter = unsafe { &mut *iter }.iter_next();
+    ffi_guard((), || {
+        if item.is_null() {
+            return;
+        }
+        unsafe { *item = null_mut() };
 
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        let Some(Value::Array(a)) = wrapper.borrow_self() else {
+            return;
+        };
+        let Some(iter) = (unsafe { PlistWrapper::from_handle(iter) }).and_then(|i| i.iter_next())
+        else {
+            return;
+        };
         if iter as usize >= a.len() {
-            unsafe { *item = null_mut() };
             return;
         }
-        let p = a.get_mut(iter as usize).unwrap();
-        let p = PlistWrapper {
-            node: NodeType::Child {
-                node: p as *mut Value,
-                parent: node as *mut Value,
-                index: iter,
-                key: None,
-            },
-            children_wrappers: Vec::new(),
-        }
-        .into_ptr();
-        wrapper.children_wrappers.push(p);
-        unsafe { *item = p };
-    }
+        unsafe { *item = wrapper.child(iter, None) };
+    })
 }
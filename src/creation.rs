@@ -1,126 +1,153 @@
 // Jackson Coxson
 
 use plist::{Dictionary, Uid, Value};
-use std::ffi::{CStr, CString, c_char};
+use std::{
+    ffi::{CString, c_char},
+    ptr::null_mut,
+};
 
-use crate::plist_t;
+use crate::{
+    NodeType, PlistWrapper, borrow_bytes, borrow_str, date, error, ffi_guard, null, plist_t,
+};
 
 /// Creates a new dictionary plist
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_dict() -> plist_t {
     let p = Value::Dictionary(Dictionary::new()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 /// Creates a new array plist
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_array() -> plist_t {
     let p = Value::Array(Vec::new()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 /// # Safety
 /// Don't pass a bad string, libplist doesn't check the string
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_new_string(val: *const c_char) -> plist_t {
-    let s = unsafe { CStr::from_ptr(val) }.to_str().unwrap();
-    let p = Value::String(s.to_string()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    ffi_guard(null_mut(), || {
+        let Some(s) = (unsafe { borrow_str(val) }) else {
+            return null_mut();
+        };
+        let p = Value::String(s.to_string()).into();
+        PlistWrapper::into_ptr(p)
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_bool(val: u8) -> plist_t {
     let p = Value::Boolean(val != 0).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_uint(val: u64) -> plist_t {
     let p = Value::Integer(val.into()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_int(val: i64) -> plist_t {
     let p = Value::Integer(val.into()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_real(val: f64) -> plist_t {
     let p = Value::Real(val).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
 /// # Safety
 /// Rust owns your data now. Don't pass a bad pointer >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_new_data(val: *const u8, length: u64) -> plist_t {
-    let slice = unsafe { std::slice::from_raw_parts(val, length as usize) }.to_vec();
-    let p = Value::Data(slice).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    ffi_guard(null_mut(), || {
+        let Ok(length) = usize::try_from(length) else {
+            return null_mut();
+        };
+        let Some(slice) = (unsafe { borrow_bytes(val, length) }) else {
+            return null_mut();
+        };
+        let p = Value::Data(slice.to_vec()).into();
+        PlistWrapper::into_ptr(p)
+    })
 }
 
-/// Don't pass a negative number >:(
+/// Creates a date from seconds since 1970, negative for earlier dates.
+/// Returns null if the system clock can't represent it.
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_unix_date(sec: i64) -> plist_t {
-    let s = std::time::UNIX_EPOCH + std::time::Duration::from_secs(sec as u64);
-    let p = Value::Date(s.into()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    ffi_guard(null_mut(), || {
+        let Some(d) = date::from_unix_secs(sec) else {
+            return null_mut();
+        };
+        let p = Value::Date(d).into();
+        PlistWrapper::into_ptr(p)
+    })
+}
+
+/// Creates a date from seconds and microseconds since the Mac epoch, 2001-01-01
+#[unsafe(no_mangle)]
+pub extern "C" fn plist_new_date(sec: i32, usec: i32) -> plist_t {
+    ffi_guard(null_mut(), || {
+        let Some(d) = date::from_unix_nanos(date::mac_to_unix_nanos(sec, usec)) else {
+            return null_mut();
+        };
+        let p = Value::Date(d).into();
+        PlistWrapper::into_ptr(p)
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_uid(val: u64) -> plist_t {
     let p = Value::Uid(Uid::new(val)).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    PlistWrapper::into_ptr(p)
 }
 
-/// So there's no null plist type in Rust, so we'll just have an empty data :shrug:
+/// There's no null plist type in Rust, so this is a date no document can hold.
+/// It turns back into a real null when written as JSON or binary.
 #[unsafe(no_mangle)]
 pub extern "C" fn plist_new_null() -> plist_t {
-    let p = Value::Data(Vec::new()).into();
-    let p = Box::new(p);
-    Box::into_raw(p)
+    let p = null::null().into();
+    PlistWrapper::into_ptr(p)
 }
 
 /// # Safety
 /// Needs to be allocated by this library
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_free(plist: plist_t) {
-    if plist.is_null() {
-        return;
-    }
-    let parent = unsafe { Box::from_raw(plist) };
-    for c in &parent.children_wrappers {
-        unsafe {
-            // recurse through the tree
-            plist_free(*c);
-        }
-    }
+    ffi_guard((), || {
+        // Dropping the wrapper recurses through the children wrappers
+        unsafe { PlistWrapper::free_handle(plist) };
+    })
 }
 
 /// # Safety
 /// Needs to be allocated by this library
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_copy(node: plist_t) -> plist_t {
-    let p = unsafe { &*node };
-    let p = Box::new(p.clone());
-    Box::into_raw(p)
+    ffi_guard(null_mut(), || {
+        let Some(p) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return null_mut();
+        };
+        if matches!(p.node, NodeType::Child { .. }) && p.borrow_self().is_none() {
+            error::set_last_error("the item behind this handle was removed from its container");
+            return null_mut();
+        }
+        p.clone().into_ptr()
+    })
 }
 
 /// # Safety
 /// Needs to be allocated by this library
 /// I sure hope nothing bad happens
 pub unsafe extern "C" fn plist_mem_free(data: *mut c_char) {
+    if data.is_null() {
+        return;
+    }
     let _ = unsafe { CString::from_raw(data) };
 }
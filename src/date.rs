@@ -0,0 +1,69 @@
+// Jackson Coxson
+// Conversions between plist dates and the second counts libplist hands around
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use plist::Date;
+
+/// Seconds between the unix epoch and the Mac epoch, 2001-01-01
+pub(crate) const MAC_EPOCH_OFFSET: i64 = 978_307_200;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Nanoseconds since the unix epoch, negative for earlier dates
+pub(crate) fn unix_nanos(date: Date) -> i128 {
+    let time: SystemTime = date.into();
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+/// Creates a date from nanoseconds since the unix epoch.
+/// Returns None if the system clock can't represent it.
+pub(crate) fn from_unix_nanos(nanos: i128) -> Option<Date> {
+    let magnitude = u64::try_from(nanos.unsigned_abs() / NANOS_PER_SEC as u128).ok()?;
+    let sub_sec = (nanos.unsigned_abs() % NANOS_PER_SEC as u128) as u32;
+    let d = Duration::new(magnitude, sub_sec);
+    let time = if nanos < 0 {
+        UNIX_EPOCH.checked_sub(d)
+    } else {
+        UNIX_EPOCH.checked_add(d)
+    }?;
+    Some(time.into())
+}
+
+/// Binary plists store dates as doubles, so round to the microsecond
+/// before splitting or 1µs comes back as 0.999µs
+fn round_to_micros(nanos: i128) -> i128 {
+    (nanos + 500 * nanos.signum()) / 1000
+}
+
+/// Whole seconds since the unix epoch, rounded down so earlier dates stay earlier
+pub(crate) fn unix_secs(date: Date) -> i64 {
+    let secs = round_to_micros(unix_nanos(date)).div_euclid(1_000_000);
+    secs.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Creates a date from seconds since the unix epoch, negative for earlier dates
+pub(crate) fn from_unix_secs(secs: i64) -> Option<Date> {
+    from_unix_nanos(secs as i128 * NANOS_PER_SEC)
+}
+
+/// Nanoseconds since the unix epoch of a Mac epoch `sec` + `usec` pair
+pub(crate) fn mac_to_unix_nanos(sec: i32, usec: i32) -> i128 {
+    (sec as i128 + MAC_EPOCH_OFFSET as i128) * NANOS_PER_SEC + usec as i128 * 1000
+}
+
+/// Splits a date into Mac epoch seconds and microseconds the way libplist does.
+/// Seconds are truncated toward zero and the microseconds are never negative.
+pub(crate) fn to_mac_sec_usec(date: Date) -> (i32, i32) {
+    let nanos = unix_nanos(date) - MAC_EPOCH_OFFSET as i128 * NANOS_PER_SEC;
+    let micros = round_to_micros(nanos);
+    let sec = micros / 1_000_000;
+    let usec = (micros % 1_000_000).abs();
+    (
+        sec.clamp(i32::MIN as i128, i32::MAX as i128) as i32,
+        usec as i32,
+    )
+}
@@ -1,23 +1,28 @@
 // Jackson Coxson
 
 use std::{
-    ffi::{CStr, CString, c_char},
+    ffi::{CString, c_char, c_void},
     ptr::null_mut,
 };
 
 use plist::Value;
 
-use crate::{NodeType, PlistWrapper, plist_dict_iter, plist_err_t, plist_t};
+use crate::{
+    NodeType, PlistErr, PlistMergeStrategy, PlistWrapper, borrow_str, borrow_value, error::fail,
+    ffi_guard, plist_dict_iter, plist_err_t, plist_t,
+};
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_get_size(node: plist_t) -> u32 {
-    if let Value::Dictionary(d) = unsafe { &mut *node }.borrow_self() {
-        d.len() as u32
-    } else {
-        0
-    }
+    ffi_guard(0, || {
+        if let Some(Value::Dictionary(d)) = unsafe { borrow_value(node) } {
+            d.len() as u32
+        } else {
+            0
+        }
+    })
 }
 
 /// Since the free function accepts an iterator, the root objects
@@ -26,8 +31,22 @@ pub unsafe extern "C" fn plist_dict_get_size(node: plist_t) -> u32 {
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_new_iter(_node: plist_t, iter: *mut plist_dict_iter) {
-    let p = PlistWrapper::new_iterator(0).into_ptr();
-    unsafe { *iter = p };
+    ffi_guard((), || {
+        if iter.is_null() {
+            return;
+        }
+        let p = PlistWrapper::new_iterator(0).into_ptr();
+        unsafe { *iter = p };
+    })
+}
+
+/// Frees an iterator from plist_dict_new_iter.
+/// Passing a node is reported through plist_get_last_error and does nothing.
+/// # Safety
+/// Don't pass a bad iterator >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_dict_free_iter(iter: plist_dict_iter) {
+    ffi_guard((), || unsafe { PlistWrapper::free_iterator(iter) })
 }
 
 /// # Safety
@@ -40,35 +59,38 @@ pub unsafe extern "C" fn plist_dict_next_item(
     key: *mut *mut c_char,
     item: *mut plist_t,
 ) {
-    let wrapper = unsafe { &mut *node };
-    let node = wrapper.borrow_self();
-
-    if let Value::Dictionary(d) = node {
-        let iter = unsafe { &mut *iter }.iter_next();
-
-        if iter as usize >= d.len() {
+    ffi_guard((), || {
+        if !item.is_null() {
             unsafe { *item = null_mut() };
-            return;
         }
-        let (p_key, p) = d.iter_mut().nth(iter as usize).unwrap();
-        let p_key = p_key.to_string();
-        let pc_key = CString::new(p_key.as_str()).unwrap();
-        let p = PlistWrapper {
-            node: NodeType::Child {
-                node: p as *mut Value,
-                parent: node as *mut Value,
-                index: u32::MAX,
-                key: Some(p_key),
-            },
-            children_wrappers: Vec::new(),
-        }
-        .into_ptr();
-        wrapper.children_wrappers.push(p);
-        unsafe {
-            *item = p;
-            *key = pc_key.into_raw();
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
         };
-    }
+        if let Some(Value::Dictionary(d)) = wrapper.borrow_self() {
+            let Some(iter) =
+                (unsafe { PlistWrapper::from_handle(iter) }).and_then(|i| i.iter_next())
+            else {
+                return;
+            };
+
+            let Some(p_key) = d.keys().nth(iter as usize) else {
+                return;
+            };
+            let p_key = p_key.to_string();
+            let Ok(pc_key) = CString::new(p_key.as_str()) else {
+                return;
+            };
+            let p = wrapper.child(u32::MAX, Some(p_key));
+            unsafe {
+                if !item.is_null() {
+                    *item = p;
+                }
+                if !key.is_null() {
+                    *key = pc_key.into_raw();
+                }
+            };
+        }
+    })
 }
 
 /// # Safety
@@ -76,131 +98,284 @@ pub unsafe extern "C" fn plist_dict_next_item(
 /// Use the system allocator or else
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_get_item_key(node: plist_t, k: *mut *mut c_char) {
-    let node = unsafe { &mut *node };
-    match &node.node {
-        NodeType::Node(_) => {}
-        NodeType::Child { key, .. } => {
-            if let Some(key) = key {
-                let key = CString::new(key.as_str()).unwrap().into_raw();
-                unsafe { *k = key };
-            }
+    ffi_guard((), || {
+        if k.is_null() {
+            return;
         }
-        NodeType::Iterator(_) => panic!("you passed an iterator as a node"),
-    };
+        let Some(node) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        if let NodeType::Child { key: Some(key), .. } = &node.node
+            && let Ok(key) = CString::new(key.as_str())
+        {
+            unsafe { *k = key.into_raw() };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_get_item(node: plist_t, key: *const c_char) -> plist_t {
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let wrapper = unsafe { &mut *node };
-    let node = wrapper.borrow_self();
-    if let Value::Dictionary(d) = node {
-        if let Some(v) = d.get_mut(key) {
-            let p = PlistWrapper {
-                node: NodeType::Child {
-                    node: v as *mut Value,
-                    parent: node as *mut Value,
-                    index: u32::MAX,
-                    key: Some(key.to_string()),
-                },
-                children_wrappers: Vec::new(),
-            }
-            .into_ptr();
-            wrapper.children_wrappers.push(p);
-            return p;
+    ffi_guard(null_mut(), || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return null_mut();
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return null_mut();
+        };
+        if let Some(Value::Dictionary(d)) = wrapper.borrow_self()
+            && d.contains_key(key)
+        {
+            return wrapper.child(u32::MAX, Some(key.to_string()));
         }
-    }
-    null_mut()
+        null_mut()
+    })
 }
 
-/// We don't have a key plist type in the plist crate
-/// We'll just assume the caller knows what they're doing.
-/// Blazing fast trust 🚀
+/// Returns a PLIST_KEY node holding the item's key.
+/// Like libplist, the key node belongs to the item, so don't free it.
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_item_get_key(node: plist_t) -> plist_t {
-    let node = unsafe { &mut *node };
-    match &node.node {
-        NodeType::Node(_) => null_mut(),
-        NodeType::Child { key, .. } => {
-            if let Some(key) = key {
-                let p = Value::String(key.to_string());
-                let p = PlistWrapper::new_node(p);
-                p.into_ptr()
-            } else {
-                null_mut()
-            }
-        }
-        NodeType::Iterator(_) => panic!("you passed an iterator as a node"),
-    }
+    ffi_guard(null_mut(), || {
+        let Some(node) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return null_mut();
+        };
+        let NodeType::Child { key: Some(key), .. } = &node.node else {
+            return null_mut();
+        };
+        let key = key.clone();
+        node.key_node(key)
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
+/// Don't move a child, the dictionary owns the item now
+/// Handles to a replaced item turn invalid
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_set_item(node: plist_t, key: *const c_char, item: plist_t) {
-    let wrapper = unsafe { &mut *node };
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let node = wrapper.borrow_self();
-    if let Value::Dictionary(d) = node {
-        let item = unsafe { *Box::from_raw(item) };
-        d.insert(
-            key.to_string(),
-            item.consume().expect("you tried to steal a child"),
-        );
-    }
+    ffi_guard((), || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return;
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        if let Some(Value::Dictionary(d)) = wrapper.borrow_self()
+            && let Some(item) = unsafe { PlistWrapper::take_handle(item) }
+            && d.insert(key.to_string(), item).is_some()
+        {
+            wrapper.release_children(|_, k| k == Some(key));
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
+/// Handles to the removed item turn invalid
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_remove_item(node: plist_t, key: *const c_char) {
-    let wrapper = unsafe { &mut *node };
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let node = wrapper.borrow_self();
-    if let Value::Dictionary(d) = node {
-        d.remove(key).expect("item doesn't exist");
-    }
+    ffi_guard((), || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return;
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return;
+        };
+        if let Some(Value::Dictionary(d)) = wrapper.borrow_self()
+            && d.remove(key).is_some()
+        {
+            wrapper.release_children(|_, k| k == Some(key));
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_merge(target: *mut plist_t, source: plist_t) {
-    let target = unsafe { &mut **target };
-    let source = unsafe { *Box::from_raw(source) }
-        .consume()
-        .expect("you tried to steal a child");
-    let node = target.borrow_self();
-
-    if let Value::Dictionary(d_target) = node
-        && let Value::Dictionary(d_source) = source
-    {
-        d_target.extend(d_source);
-    }
+    ffi_guard((), || {
+        if target.is_null() {
+            return;
+        }
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(*target) }) else {
+            return;
+        };
+        let Some(Value::Dictionary(d_target)) = wrapper.borrow_self() else {
+            return;
+        };
+        // A child can't be moved, so it's copied instead
+        let d_source = match unsafe { PlistWrapper::from_handle(source) } {
+            Some(s) if matches!(s.node, NodeType::Child { .. }) => s.borrow_self().cloned(),
+            _ => unsafe { PlistWrapper::take_handle(source) },
+        };
+        if let Some(Value::Dictionary(d_source)) = d_source {
+            let replaced: Vec<String> = d_source
+                .keys()
+                .filter(|k| d_target.contains_key(k))
+                .cloned()
+                .collect();
+            d_target.extend(d_source);
+            wrapper.release_children(|_, k| k.is_some_and(|k| replaced.iter().any(|r| r == k)));
+        }
 
-    // no need to change the pointer since we modified the target in-memory
+        // no need to change the pointer since we modified the target in-memory
+    })
 }
 
+/// Decides a conflict for PLIST_MERGE_CALLBACK.
+/// Gets the key, the target's value and the source's value, both only valid during the call.
+/// The target's value may be changed in place before returning PLIST_MERGE_KEEP.
+/// Returns a PlistMergeStrategy, anything else stops the merge.
+#[allow(non_camel_case_types)]
+pub type plist_merge_cb_t = Option<
+    unsafe extern "C" fn(
+        key: *const c_char,
+        target: plist_t,
+        source: plist_t,
+        user_data: *mut c_void,
+    ) -> u32,
+>;
+
+/// Merges the source dictionary into the target, recursing into dictionaries both have.
+/// The source is only read, so it can be a child and is still the caller's to free.
+/// The strategy is a PlistMergeStrategy, the callback is only used with PLIST_MERGE_CALLBACK.
+/// If the callback answers with something else the merge stops there, keeping what it did so far.
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn plist_dict_get_bool(dict: plist_t, key: *const c_char) -> u8 {
-    let node = unsafe { &mut *dict }.borrow_self();
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-
-    if let Value::Dictionary(d) = node {
-        match internal_get_bool(d, key) {
-            Some(true) => 1,
-            Some(false) => 0,
-            None => 0,
+pub unsafe extern "C" fn plist_dict_merge_deep(
+    target: *mut plist_t,
+    source: plist_t,
+    strategy: u32,
+    callback: plist_merge_cb_t,
+    user_data: *mut c_void,
+) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        if target.is_null() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "target is null");
+        }
+        let strategy = match PlistMergeStrategy::try_from(strategy) {
+            Ok(s) => s,
+            Err(e) => return fail(PlistErr::PLIST_ERR_INVALID_ARG, e),
+        };
+        if strategy == PlistMergeStrategy::PLIST_MERGE_CALLBACK && callback.is_none() {
+            return fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                "PLIST_MERGE_CALLBACK needs a callback",
+            );
+        }
+        // Copied up front, so merging a dictionary into itself or its parent is fine
+        let Some(Value::Dictionary(source)) = (unsafe { borrow_value(source) }).cloned() else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "source isn't a dictionary");
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(*target) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid target handle");
+        };
+        if !matches!(wrapper.borrow_self(), Some(Value::Dictionary(_))) {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "target isn't a dictionary");
+        }
+        match merge_deep(wrapper, source, strategy, callback, user_data) {
+            Ok(()) => PlistErr::PLIST_ERR_SUCCESS,
+            Err(e) => fail(PlistErr::PLIST_ERR_INVALID_ARG, e),
+        }
+    })
+}
+
+fn merge_deep(
+    target: &mut PlistWrapper,
+    source: plist::Dictionary,
+    strategy: PlistMergeStrategy,
+    callback: plist_merge_cb_t,
+    user_data: *mut c_void,
+) -> Result<(), String> {
+    for (key, s) in source {
+        let Some(Value::Dictionary(d)) = target.borrow_self() else {
+            return Ok(());
+        };
+        let s = match (d.get_mut(&key), s) {
+            (None, s) => {
+                d.insert(key, s);
+                continue;
+            }
+            (Some(Value::Dictionary(_)), Value::Dictionary(s)) => {
+                // Through the child wrapper, so handles the caller holds stay in sync
+                let child = target.child(u32::MAX, Some(key));
+                if let Some(child) = unsafe { PlistWrapper::from_handle(child) } {
+                    merge_deep(child, s, strategy, callback, user_data)?;
+                }
+                continue;
+            }
+            (Some(_), s) => s,
+        };
+
+        let strategy = match (strategy, callback) {
+            (PlistMergeStrategy::PLIST_MERGE_CALLBACK, Some(callback)) => {
+                let Ok(c_key) = CString::new(key.as_str()) else {
+                    continue;
+                };
+                let t = target.child(u32::MAX, Some(key.clone()));
+                let s_handle = PlistWrapper::new_node(s.clone()).into_ptr();
+                let decided = unsafe { callback(c_key.as_ptr(), t, s_handle, user_data) };
+                unsafe { PlistWrapper::free_handle(s_handle) };
+                PlistMergeStrategy::try_from(decided).map_err(|_| {
+                    format!(
+                        "the callback for {key:?} answered {decided}, which isn't a merge strategy"
+                    )
+                })?
+            }
+            (strategy, _) => strategy,
+        };
+
+        // The callback may have changed the target, so look again
+        let Some(Value::Dictionary(d)) = target.borrow_self() else {
+            return Ok(());
+        };
+        let Some(t) = d.get_mut(&key) else {
+            continue;
+        };
+        match (strategy, t, s) {
+            (PlistMergeStrategy::PLIST_MERGE_CONCAT_ARRAYS, Value::Array(t), Value::Array(s)) => {
+                t.extend(s);
+            }
+            (
+                PlistMergeStrategy::PLIST_MERGE_OVERWRITE
+                | PlistMergeStrategy::PLIST_MERGE_CONCAT_ARRAYS,
+                t,
+                s,
+            ) => {
+                *t = s;
+                target.release_children(|_, k| k == Some(key.as_str()));
+            }
+            // Keep, or a callback that answered with itself
+            _ => {}
         }
-    } else {
-        0
     }
+    Ok(())
+}
+
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_dict_get_bool(dict: plist_t, key: *const c_char) -> u8 {
+    ffi_guard(0, || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return 0;
+        };
+
+        if let Some(Value::Dictionary(d)) = unsafe { borrow_value(dict) } {
+            match internal_get_bool(d, key) {
+                Some(true) => 1,
+                Some(false) => 0,
+                None => 0,
+            }
+        } else {
+            0
+        }
+    })
 }
 
 fn internal_get_bool(d: &mut plist::Dictionary, key: &str) -> Option<bool> {
@@ -243,14 +418,17 @@ fn internal_get_bool(d: &mut plist::Dictionary, key: &str) -> Option<bool> {
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_get_int(dict: plist_t, key: *const c_char) -> i64 {
-    let node = unsafe { &mut *dict }.borrow_self();
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
+    ffi_guard(0, || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return 0;
+        };
 
-    if let Value::Dictionary(d) = node {
-        internal_get_i64(d, key).unwrap_or_default()
-    } else {
-        0
-    }
+        if let Some(Value::Dictionary(d)) = unsafe { borrow_value(dict) } {
+            internal_get_i64(d, key).unwrap_or_default()
+        } else {
+            0
+        }
+    })
 }
 
 fn internal_get_i64(d: &mut plist::Dictionary, key: &str) -> Option<i64> {
@@ -301,14 +479,17 @@ fn internal_get_i64(d: &mut plist::Dictionary, key: &str) -> Option<i64> {
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_dict_get_uint(dict: plist_t, key: *const c_char) -> u64 {
-    let node = unsafe { &mut *dict }.borrow_self();
-    let key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
+    ffi_guard(0, || {
+        let Some(key) = (unsafe { borrow_str(key) }) else {
+            return 0;
+        };
 
-    if let Value::Dictionary(d) = node {
-        internal_get_u64(d, key).unwrap_or_default()
-    } else {
-        0
-    }
+        if let Some(Value::Dictionary(d)) = unsafe { borrow_value(dict) } {
+            internal_get_u64(d, key).unwrap_or_default()
+        } else {
+            0
+        }
+    })
 }
 
 fn internal_get_u64(d: &mut plist::Dictionary, key: &str) -> Option<u64> {
@@ -378,28 +559,16 @@ pub unsafe extern "C" fn plist_dict_copy_item(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let lookup_key = if alt_source_key.is_null() {
-        insert_key
-    } else {
-        unsafe { CStr::from_ptr(alt_source_key) }.to_str().unwrap()
-    };
-
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        if let Some(to_copy) = d_source.get(lookup_key) {
-            let to_copy = to_copy.clone();
-            d_target.insert(insert_key.to_string(), to_copy);
-            plist_err_t::PLIST_ERR_SUCCESS
-        } else {
-            plist_err_t::PLIST_ERR_INVALID_ARG
-        }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "a value",
+            |d, k| d.get(k).cloned(),
+        )
+    })
 }
 
 /// # Safety
@@ -411,29 +580,16 @@ pub unsafe extern "C" fn plist_dict_copy_bool(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
-    let lookup_key = if alt_source_key.is_null() {
-        key
-    } else {
-        alt_source_key
-    };
-    let lookup_key = unsafe { CStr::from_ptr(lookup_key) }.to_str().unwrap();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        match internal_get_bool(d_source, lookup_key) {
-            Some(b) => {
-                let p = Value::Boolean(b);
-                d_target.insert(insert_key.to_string(), p);
-                plist_err_t::PLIST_ERR_SUCCESS
-            }
-            None => plist_err_t::PLIST_ERR_INVALID_ARG,
-        }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "a boolean",
+            |d, k| internal_get_bool(d, k).map(Value::Boolean),
+        )
+    })
 }
 
 /// # Safety
@@ -445,29 +601,16 @@ pub unsafe extern "C" fn plist_dict_copy_int(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
-    let lookup_key = if alt_source_key.is_null() {
-        key
-    } else {
-        alt_source_key
-    };
-    let lookup_key = unsafe { CStr::from_ptr(lookup_key) }.to_str().unwrap();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        match internal_get_i64(d_source, lookup_key) {
-            Some(i) => {
-                let p = Value::Integer(i.into());
-                d_target.insert(insert_key.to_string(), p);
-                plist_err_t::PLIST_ERR_SUCCESS
-            }
-            None => plist_err_t::PLIST_ERR_INVALID_ARG,
-        }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "an integer",
+            |d, k| internal_get_i64(d, k).map(|i| Value::Integer(i.into())),
+        )
+    })
 }
 
 /// # Safety
@@ -479,29 +622,16 @@ pub unsafe extern "C" fn plist_dict_copy_uint(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
-    let lookup_key = if alt_source_key.is_null() {
-        key
-    } else {
-        alt_source_key
-    };
-    let lookup_key = unsafe { CStr::from_ptr(lookup_key) }.to_str().unwrap();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        match internal_get_u64(d_source, lookup_key) {
-            Some(i) => {
-                let p = Value::Integer(i.into());
-                d_target.insert(insert_key.to_string(), p);
-                plist_err_t::PLIST_ERR_SUCCESS
-            }
-            None => plist_err_t::PLIST_ERR_INVALID_ARG,
-        }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "an unsigned integer",
+            |d, k| internal_get_u64(d, k).map(|i| Value::Integer(i.into())),
+        )
+    })
 }
 
 /// # Safety
@@ -513,28 +643,19 @@ pub unsafe extern "C" fn plist_dict_copy_data(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
-    let lookup_key = if alt_source_key.is_null() {
-        key
-    } else {
-        alt_source_key
-    };
-    let lookup_key = unsafe { CStr::from_ptr(lookup_key) }.to_str().unwrap();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        if let Some(Value::Data(d)) = d_source.get(lookup_key) {
-            let d = Value::Data(d.clone());
-            d_target.insert(insert_key.to_string(), d);
-            plist_err_t::PLIST_ERR_SUCCESS
-        } else {
-            plist_err_t::PLIST_ERR_INVALID_ARG
-        }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "data",
+            |d, k| match d.get(k) {
+                Some(Value::Data(d)) => Some(Value::Data(d.clone())),
+                _ => None,
+            },
+        )
+    })
 }
 
 /// # Safety
@@ -546,26 +667,77 @@ pub unsafe extern "C" fn plist_dict_copy_string(
     key: *const c_char,
     alt_source_key: *const c_char,
 ) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        copy_entry(
+            target_plist,
+            source_plist,
+            key,
+            alt_source_key,
+            "a string",
+            |d, k| match d.get(k) {
+                Some(Value::String(s)) => Some(Value::String(s.clone())),
+                _ => None,
+            },
+        )
+    })
+}
+
+/// The body of the plist_dict_copy_* functions.
+/// `convert` reads the entry out of the source, None if it isn't `what`.
+unsafe fn copy_entry(
+    target_plist: plist_t,
+    source_plist: plist_t,
+    key: *const c_char,
+    alt_source_key: *const c_char,
+    what: &str,
+    convert: impl FnOnce(&mut plist::Dictionary, &str) -> Option<Value>,
+) -> plist_err_t {
+    let Some(insert_key) = (unsafe { borrow_str(key) }) else {
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            "key is null or not UTF-8",
+        );
+    };
     let lookup_key = if alt_source_key.is_null() {
-        key
+        insert_key
     } else {
-        alt_source_key
-    };
-    let lookup_key = unsafe { CStr::from_ptr(lookup_key) }.to_str().unwrap();
-    let insert_key = unsafe { CStr::from_ptr(key) }.to_str().unwrap();
-    let target_plist = unsafe { &mut *target_plist }.borrow_self();
-    let source_plist = unsafe { &mut *source_plist }.borrow_self();
-    if let Value::Dictionary(d_target) = target_plist
-        && let Value::Dictionary(d_source) = source_plist
-    {
-        if let Some(Value::String(s)) = d_source.get(lookup_key) {
-            let d = Value::String(s.clone());
-            d_target.insert(insert_key.to_string(), d);
-            plist_err_t::PLIST_ERR_SUCCESS
-        } else {
-            plist_err_t::PLIST_ERR_INVALID_ARG
+        match unsafe { borrow_str(alt_source_key) } {
+            Some(k) => k,
+            None => {
+                return fail(
+                    plist_err_t::PLIST_ERR_INVALID_ARG,
+                    "alt_source_key isn't UTF-8",
+                );
+            }
         }
-    } else {
-        plist_err_t::PLIST_ERR_INVALID_ARG
+    };
+
+    // Read the source before borrowing the target, they can be the same dictionary
+    let Some(Value::Dictionary(d_source)) = (unsafe { borrow_value(source_plist) }) else {
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            "source isn't a dictionary",
+        );
+    };
+    if !d_source.contains_key(lookup_key) {
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            format_args!("{lookup_key:?} doesn't exist"),
+        );
     }
+    let Some(value) = convert(d_source, lookup_key) else {
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            format_args!("{lookup_key:?} isn't {what}"),
+        );
+    };
+
+    let Some(Value::Dictionary(d_target)) = (unsafe { borrow_value(target_plist) }) else {
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            "target isn't a dictionary",
+        );
+    };
+    d_target.insert(insert_key.to_string(), value);
+    plist_err_t::PLIST_ERR_SUCCESS
 }
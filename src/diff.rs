@@ -0,0 +1,91 @@
+// Jackson Coxson
+// Structural diff between two plists, reported as a plist array of change records
+
+use plist::{Dictionary, Value};
+
+use crate::{
+    PlistErr, PlistWrapper, borrow_value, error::fail, ffi_guard, path::join_path, plist_err_t,
+    plist_t,
+};
+
+/// Walks both trees, recording every place they differ
+fn walk(old: &Value, new: &Value, path: &mut Vec<String>, changes: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Dictionary(o), Value::Dictionary(n)) => {
+            for (key, o) in o {
+                path.push(key.clone());
+                match n.get(key) {
+                    Some(n) => walk(o, n, path, changes),
+                    None => changes.push(record("remove", path, Some(o), None)),
+                }
+                path.pop();
+            }
+            for (key, n) in n.iter().filter(|(k, _)| !o.contains_key(k)) {
+                path.push(key.clone());
+                changes.push(record("add", path, None, Some(n)));
+                path.pop();
+            }
+        }
+        (Value::Array(o), Value::Array(n)) => {
+            for (i, (o, n)) in o.iter().zip(n).enumerate() {
+                path.push(i.to_string());
+                walk(o, n, path, changes);
+                path.pop();
+            }
+            // Removals from the back, so applying them in order keeps the indexes right
+            for (i, o) in o.iter().enumerate().skip(n.len()).rev() {
+                path.push(i.to_string());
+                changes.push(record("remove", path, Some(o), None));
+                path.pop();
+            }
+            for (i, n) in n.iter().enumerate().skip(o.len()) {
+                path.push(i.to_string());
+                changes.push(record("add", path, None, Some(n)));
+                path.pop();
+            }
+        }
+        (o, n) if o != n => changes.push(record("change", path, Some(o), Some(n))),
+        _ => {}
+    }
+}
+
+fn record(op: &str, path: &[String], old: Option<&Value>, new: Option<&Value>) -> Value {
+    let mut d = Dictionary::new();
+    d.insert("op".to_string(), Value::String(op.to_string()));
+    d.insert("path".to_string(), Value::String(join_path(path)));
+    if let Some(old) = old {
+        d.insert("old".to_string(), old.clone());
+    }
+    if let Some(new) = new {
+        d.insert("new".to_string(), new.clone());
+    }
+    Value::Dictionary(d)
+}
+
+/// Compares two plists and returns an array of change records, empty if they're equal.
+/// Each record is a dictionary with `op` (`add`, `remove` or `change`),
+/// `path` (like `:Devices:0:UDID`, see plist_path_get_item),
+/// and `old` and `new` holding the values on either side, when there is one.
+/// Free the result with plist_free.
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_diff(old: plist_t, new: plist_t, diff: *mut plist_t) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        if diff.is_null() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "diff is null");
+        }
+        unsafe { *diff = std::ptr::null_mut() };
+        let (Some(o), Some(n)) = (unsafe { borrow_value(old) }, unsafe { borrow_value(new) })
+        else {
+            return fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                "both plists have to be nodes",
+            );
+        };
+        let mut changes = Vec::new();
+        walk(o, n, &mut Vec::new(), &mut changes);
+        unsafe { *diff = PlistWrapper::new_node(Value::Array(changes)).into_ptr() };
+        PlistErr::PLIST_ERR_SUCCESS
+    })
+}
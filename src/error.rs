@@ -0,0 +1,45 @@
+// Jackson Coxson
+
+use std::{
+    cell::RefCell,
+    ffi::{CString, c_char},
+    fmt::Display,
+    ptr::null,
+};
+
+use crate::plist_err_t;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records a message for `plist_get_last_error` on this thread
+pub(crate) fn set_last_error(message: impl Display) {
+    // Interior nulls would cut the message short anyway
+    let message = message.to_string().replace('\0', " ");
+    let message = CString::new(message).ok();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+/// Records a message and hands back the error code, for `return fail(...)`
+pub(crate) fn fail(code: plist_err_t, message: impl Display) -> plist_err_t {
+    set_last_error(message);
+    code
+}
+
+/// Returns a description of the last error on this thread, including the
+/// line, column or byte offset when the parser reported one.
+/// Returns null if nothing has failed since the last clear.
+/// Like errno, successful calls don't reset it.
+/// The string belongs to the library and is valid until the next failing call
+/// or `plist_clear_last_error` on the same thread, don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn plist_get_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|e| e.as_ptr()).unwrap_or(null()))
+}
+
+/// Forgets the last error on this thread
+#[unsafe(no_mangle)]
+pub extern "C" fn plist_clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
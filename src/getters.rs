@@ -7,77 +7,87 @@ use std::{
 
 use plist::Value;
 
-use crate::{NodeType, PlistType, PlistWrapper, plist_t};
+use crate::{
+    NodeType, PlistType, PlistWrapper, borrow_key, borrow_value, date, ffi_guard, null, plist_t,
+};
+
+/// Copies a string into a C string the caller must free.
+/// Interior nulls can't be represented, so the string is cut at the first one.
+fn to_c_string(s: &str) -> *mut c_char {
+    let s = s.split('\0').next().unwrap_or_default();
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(_) => null_mut(),
+    }
+}
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_parent(node: plist_t) -> plist_t {
-    let wrapper = unsafe { &mut *node };
-    if let NodeType::Child { parent, node, .. } = wrapper.node {
-        // we'll return the parent as a child wrapper
-        // might cause issues, who knows
-        // for example, if someone uses this parent to add it to another dictionary, it will panic
-        // that you tried to move a child
-        let parent = unsafe { &mut *parent };
-        let p = PlistWrapper {
-            node: NodeType::Child {
-                node: parent as *mut Value,
-                parent: node,
-                index: u32::MAX,
-                key: None,
-            },
-            children_wrappers: Vec::new(),
-        }
-        .into_ptr();
-        wrapper.children_wrappers.push(p);
-        return p;
-    }
-    null_mut()
+    ffi_guard(null_mut(), || {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(node) }) else {
+            return null_mut();
+        };
+        // The parent owns this handle, so it's live for as long as we are
+        match wrapper.node {
+            NodeType::Child { parent, .. } => parent as plist_t,
+            _ => null_mut(),
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_node_type(node: plist_t) -> PlistType {
-    let node = unsafe { &mut *node }.borrow_self();
-    match node {
-        Value::Array(_) => PlistType::PLIST_ARRAY,
-        Value::Dictionary(_) => PlistType::PLIST_DICT,
-        Value::Boolean(_) => PlistType::PLIST_BOOLEAN,
-        Value::Data(_) => PlistType::PLIST_DATA,
-        Value::Date(_) => PlistType::PLIST_DATE,
-        Value::Real(_) => PlistType::PLIST_REAL,
-        Value::Integer(_) => PlistType::PLIST_INT,
-        Value::String(_) => PlistType::PLIST_STRING,
-        Value::Uid(_) => PlistType::PLIST_UID,
-        _ => PlistType::PLIST_NONE,
-    }
+    ffi_guard(PlistType::PLIST_NONE, || {
+        if unsafe { borrow_key(node) }.is_some() {
+            return PlistType::PLIST_KEY;
+        }
+        match unsafe { borrow_value(node) } {
+            Some(Value::Array(_)) => PlistType::PLIST_ARRAY,
+            Some(Value::Dictionary(_)) => PlistType::PLIST_DICT,
+            Some(Value::Boolean(_)) => PlistType::PLIST_BOOLEAN,
+            Some(Value::Data(_)) => PlistType::PLIST_DATA,
+            Some(v) if null::is_null(v) => PlistType::PLIST_NULL,
+            Some(Value::Date(_)) => PlistType::PLIST_DATE,
+            Some(Value::Real(_)) => PlistType::PLIST_REAL,
+            Some(Value::Integer(_)) => PlistType::PLIST_INT,
+            Some(Value::String(_)) => PlistType::PLIST_STRING,
+            Some(Value::Uid(_)) => PlistType::PLIST_UID,
+            _ => PlistType::PLIST_NONE,
+        }
+    })
 }
 
-/// We don't have a key type, so we'll return a string and hope everything is fine
+/// Only works on PLIST_KEY nodes, like the ones from plist_dict_item_get_key
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_key_val(node: plist_t, val: *mut *mut c_char) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::String(s) = node {
-        let s = CString::new(s.to_string()).unwrap();
-        let s = s.into_raw();
-        unsafe { *val = s };
-    }
+    ffi_guard((), || {
+        if val.is_null() {
+            return;
+        }
+        if let Some(key) = unsafe { borrow_key(node) } {
+            unsafe { *val = to_c_string(key) };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_string_val(node: plist_t, val: *mut *mut c_char) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::String(s) = node {
-        let s = CString::new(s.to_string()).unwrap();
-        let s = s.into_raw();
-        unsafe { *val = s };
-    }
+    ffi_guard((), || {
+        if val.is_null() {
+            return;
+        }
+        if let Some(Value::String(s)) = unsafe { borrow_value(node) } {
+            unsafe { *val = to_c_string(s) };
+        }
+    })
 }
 
 /// Since the underlying string isn't necessarily "C safe", we'll cross our fingers
@@ -86,118 +96,169 @@ pub unsafe extern "C" fn plist_get_string_val(node: plist_t, val: *mut *mut c_ch
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_string_ptr(node: plist_t, length: *mut u64) -> *const c_char {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::String(s) = node {
-        unsafe { *length = s.len() as u64 };
-        s.as_ptr() as *const c_char
-    } else {
-        null_mut()
-    }
+    ffi_guard(null_mut(), || {
+        if let Some(Value::String(s)) = unsafe { borrow_value(node) } {
+            if !length.is_null() {
+                unsafe { *length = s.len() as u64 };
+            }
+            s.as_ptr() as *const c_char
+        } else {
+            null_mut()
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_bool_val(node: plist_t, val: *mut u8) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Boolean(b) = node {
-        unsafe { *val = if *b { 1 } else { 0 } };
-    }
+    ffi_guard((), || {
+        if let Some(Value::Boolean(b)) = unsafe { borrow_value(node) }
+            && !val.is_null()
+        {
+            unsafe { *val = if *b { 1 } else { 0 } };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_uint_val(node: plist_t, val: *mut u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Integer(n) = node
-        && let Some(u) = n.as_unsigned()
-    {
-        unsafe { *val = u };
-    }
+    ffi_guard((), || {
+        if let Some(Value::Integer(n)) = unsafe { borrow_value(node) }
+            && let Some(u) = n.as_unsigned()
+            && !val.is_null()
+        {
+            unsafe { *val = u };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_int_val(node: plist_t, val: *mut i64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Integer(n) = node
-        && let Some(i) = n.as_signed()
-    {
-        unsafe { *val = i };
-    }
+    ffi_guard((), || {
+        if let Some(Value::Integer(n)) = unsafe { borrow_value(node) }
+            && let Some(i) = n.as_signed()
+            && !val.is_null()
+        {
+            unsafe { *val = i };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_real_val(node: plist_t, val: *mut f64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Real(r) = node {
-        unsafe { *val = *r };
-    }
+    ffi_guard((), || {
+        if let Some(Value::Real(r)) = unsafe { borrow_value(node) }
+            && !val.is_null()
+        {
+            unsafe { *val = *r };
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_data_val(node: plist_t, val: *mut *const u8, length: *mut u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Data(d) = node {
-        // Clone the data and add a null terminator
-        let mut data_with_null = d.clone();
-        data_with_null.push(0);
+    ffi_guard((), || {
+        if val.is_null() || length.is_null() {
+            return;
+        }
+        if let Some(Value::Data(d)) = unsafe { borrow_value(node) } {
+            // Clone the data and add a null terminator
+            let mut data_with_null = d.clone();
+            data_with_null.push(0);
 
-        // Convert to boxed slice to heap-allocate
-        let boxed: Box<[u8]> = data_with_null.into_boxed_slice();
-        let ptr = boxed.as_ptr();
+            // Convert to boxed slice to heap-allocate
+            let boxed: Box<[u8]> = data_with_null.into_boxed_slice();
+            let ptr = boxed.as_ptr();
 
-        // Return original length (excluding null terminator)
-        unsafe {
-            *val = ptr;
-            *length = (boxed.len() - 1) as u64;
-        }
+            // Return original length (excluding null terminator)
+            unsafe {
+                *val = ptr;
+                *length = (boxed.len() - 1) as u64;
+            }
 
-        // Prevent Rust from freeing it - caller must free
-        std::mem::forget(boxed);
+            // Prevent Rust from freeing it - caller must free
+            std::mem::forget(boxed);
 
-        // Plug ears for explosion
-    }
+            // Plug ears for explosion
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_data_ptr(node: plist_t, length: *mut u64) -> *const c_char {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Data(d) = node {
-        unsafe { *length = d.len() as u64 };
-        d.as_ptr() as *const c_char
-    } else {
-        null_mut()
-    }
+    ffi_guard(null_mut(), || {
+        if let Some(Value::Data(d)) = unsafe { borrow_value(node) } {
+            if !length.is_null() {
+                unsafe { *length = d.len() as u64 };
+            }
+            d.as_ptr() as *const c_char
+        } else {
+            null_mut()
+        }
+    })
 }
 
+/// Gets seconds since 1970, negative for earlier dates.
+/// Fractions are rounded down, so a date never reads as later than it is.
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_unix_date_val(node: plist_t, secs: *mut i64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Date(d) = node {
-        let d = *d;
-        let d: std::time::SystemTime = d.into();
-        let d = d.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
-        unsafe { *secs = d };
-    }
+    ffi_guard((), || {
+        if secs.is_null() {
+            return;
+        }
+        if let Some(v) = unsafe { borrow_value(node) }
+            && !null::is_null(v)
+            && let Value::Date(d) = v
+        {
+            unsafe { *secs = date::unix_secs(*d) };
+        }
+    })
+}
+
+/// Gets seconds and microseconds since the Mac epoch, 2001-01-01
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_get_date_val(node: plist_t, sec: *mut i32, usec: *mut i32) {
+    ffi_guard((), || {
+        if sec.is_null() || usec.is_null() {
+            return;
+        }
+        if let Some(v) = unsafe { borrow_value(node) }
+            && !null::is_null(v)
+            && let Value::Date(d) = v
+        {
+            let (s, u) = date::to_mac_sec_usec(*d);
+            unsafe {
+                *sec = s;
+                *usec = u;
+            }
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_get_uid_val(node: plist_t, val: *mut u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    if let Value::Uid(u) = node {
-        unsafe { *val = u.get() };
-    }
+    ffi_guard((), || {
+        if let Some(Value::Uid(u)) = unsafe { borrow_value(node) }
+            && !val.is_null()
+        {
+            unsafe { *val = u.get() };
+        }
+    })
 }
@@ -0,0 +1,47 @@
+// Jackson Coxson
+// Every wrapper handed out to C is kept in a registry, so a stale, double freed
+// or made up handle is turned away instead of being dereferenced.
+// A freed address that the allocator hands out again will look valid,
+// nothing short of never freeing could catch that.
+
+use std::{
+    collections::HashMap,
+    ptr::null_mut,
+    sync::{LazyLock, Mutex, MutexGuard},
+};
+
+use crate::PlistWrapper;
+
+/// Live handles, along with the wrapper that owns them, 0 for roots
+static HANDLES: LazyLock<Mutex<HashMap<usize, usize>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn handles() -> MutexGuard<'static, HashMap<usize, usize>> {
+    // A panic while holding the lock can't leave the map half updated
+    HANDLES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records a freshly boxed wrapper as live.
+/// Children pass the wrapper whose `children_wrappers` they're in, roots pass null.
+pub(crate) fn register(handle: *mut PlistWrapper, owner: *mut PlistWrapper) {
+    handles().insert(handle as usize, owner as usize);
+}
+
+/// Forgets a wrapper that's about to be freed
+pub(crate) fn unregister(handle: *const PlistWrapper) {
+    handles().remove(&(handle as usize));
+}
+
+/// Whether the handle points at a live wrapper
+pub(crate) fn is_live(handle: *const PlistWrapper) -> bool {
+    handles().contains_key(&(handle as usize))
+}
+
+/// The live wrapper owning the handle, null for roots
+pub(crate) fn owner(handle: *const PlistWrapper) -> *mut PlistWrapper {
+    let handles = handles();
+    match handles.get(&(handle as usize)) {
+        Some(owner) if *owner != 0 && handles.contains_key(owner) => *owner as *mut PlistWrapper,
+        _ => null_mut(),
+    }
+}
@@ -1,69 +1,333 @@
 // Jackson Coxson
 
-use std::ffi::{CStr, c_char, c_void};
+use std::{
+    ffi::{c_char, c_int, c_void},
+    io::{BufWriter, Read, Write},
+};
 
 use plist::Value;
 
-use crate::{PLIST_OPT_INDENT, PlistFormat, PlistWrapper, PlistWriteOptions, plist_err_t, plist_t};
-
-/// # Safety
-/// Don't pass a bad plist >:(
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn plist_to_xml(
-    node: plist_t,
-    plist_xml: *mut *mut c_char,
-    length: *mut u32,
+use crate::{
+    PLIST_OPT_COMPACT, PLIST_OPT_NO_NEWLINE, PLIST_OPT_PARTIAL_DATA, PlistFormat, PlistWrapper,
+    PlistWriteOptions, borrow_bytes, borrow_str, borrow_value, error::fail, ffi_guard, json, null,
+    openstep, plist_err_t, plist_t, print, xml,
+};
+
+/// Hands a serialized buffer over to the caller, with the length as a u32 or u64.
+/// It's null terminated so it can be used as a C string, the caller must free it.
+/// Buffers too long for the length are an error rather than truncated.
+unsafe fn hand_off_buffer<L: TryFrom<usize>>(
+    mut data: Vec<u8>,
+    output: *mut *mut c_char,
+    length: *mut L,
 ) -> plist_err_t {
-    let node = unsafe { &mut *node }.borrow_self();
-
-    let buf = Vec::new();
-    let mut writer = std::io::BufWriter::new(buf);
-    plist::to_writer_xml(&mut writer, node).unwrap();
-    let mut xml = writer.into_inner().unwrap();
-    xml.push(0);
-    let mut boxed = xml.into_boxed_slice();
+    if output.is_null() || length.is_null() {
+        return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "output pointer is null");
+    }
+    let Ok(len) = L::try_from(data.len()) else {
+        unsafe { *output = std::ptr::null_mut() };
+        return fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            format_args!(
+                "{} bytes don't fit in a 32-bit length, use the 64-bit variant",
+                data.len()
+            ),
+        );
+    };
+    data.push(0);
+    let mut boxed = data.into_boxed_slice();
 
     let ptr = boxed.as_mut_ptr();
 
     // Return original length (excluding null terminator)
     unsafe {
-        *plist_xml = ptr as *mut c_char;
-        *length = (boxed.len() - 1) as u32;
+        *output = ptr as *mut c_char;
+        *length = len;
     }
 
-    // Prevent Rust from freeing it — caller must free
+    // Prevent Rust from freeing it - caller must free
     std::mem::forget(boxed);
     plist_err_t::PLIST_ERR_SUCCESS
 }
 
+/// Serializes the node into a buffer for the caller.
+/// On failure the output is set to null, if there is one.
+unsafe fn write_to_buffer<L: TryFrom<usize>>(
+    node: plist_t,
+    format: PlistFormat,
+    options: PlistWriteOptions,
+    output: *mut *mut c_char,
+    length: *mut L,
+) -> plist_err_t {
+    let Some(node) = (unsafe { borrow_value(node) }) else {
+        return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+    };
+    match serialize(node, format, options) {
+        Ok(data) => unsafe { hand_off_buffer(data, output, length) },
+        Err(e) => {
+            if !output.is_null() {
+                unsafe { *output = std::ptr::null_mut() };
+            }
+            e
+        }
+    }
+}
+
+/// Borrows the caller's input buffer
+unsafe fn borrow_input<'a>(data: *const c_char, length: u64) -> Result<&'a [u8], plist_err_t> {
+    let Ok(length) = usize::try_from(length) else {
+        return Err(fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            "length is larger than the address space",
+        ));
+    };
+    unsafe { borrow_bytes(data as *const u8, length) }
+        .ok_or_else(|| fail(plist_err_t::PLIST_ERR_INVALID_ARG, "input buffer is null"))
+}
+
+/// Hands a freshly parsed tree over to the caller
+unsafe fn hand_off_node(value: Value, plist: *mut plist_t) -> plist_err_t {
+    if plist.is_null() {
+        return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "output pointer is null");
+    }
+    let p = PlistWrapper::new_node(value).into_ptr();
+    unsafe { *plist = p };
+    plist_err_t::PLIST_ERR_SUCCESS
+}
+
+/// Serializes a value into the given format
+fn serialize(
+    value: &Value,
+    format: PlistFormat,
+    options: PlistWriteOptions,
+) -> Result<Vec<u8>, plist_err_t> {
+    let mut buf = Vec::new();
+    serialize_to(value, format, options, &mut buf)?;
+    Ok(buf)
+}
+
+/// Serializes a value into the given format, writing it out as it goes.
+/// Failed writes are PLIST_ERR_IO.
+fn serialize_to(
+    value: &Value,
+    format: PlistFormat,
+    options: PlistWriteOptions,
+    out: &mut impl Write,
+) -> Result<(), plist_err_t> {
+    let compact = options & PLIST_OPT_COMPACT != 0;
+    // libplist ends text documents with a newline
+    let newline: &[u8] = if options & PLIST_OPT_NO_NEWLINE != 0 {
+        b""
+    } else {
+        b"\n"
+    };
+    let plist_err = |what: &str, e: plist::Error| match e.into_io() {
+        Ok(e) => io_err(e),
+        Err(e) => fail(plist_err_t::PLIST_ERR_UNKNOWN, format_args!("{what}: {e}")),
+    };
+
+    match format {
+        PlistFormat::PLIST_FORMAT_XML => {
+            if null::contains_null(value) {
+                return Err(fail(
+                    plist_err_t::PLIST_ERR_FORMAT,
+                    "XML can't represent null",
+                ));
+            }
+            if compact {
+                // Not a single line break, the last one included
+                return out.write_all(&xml::to_compact_xml(value)?).map_err(io_err);
+            }
+            out.write_all(&xml::to_xml(value)?).map_err(io_err)?;
+            out.write_all(newline).map_err(io_err)
+        }
+        PlistFormat::PLIST_FORMAT_BINARY => {
+            if null::contains_null(value) {
+                // The nulls get patched in afterwards, so this one is buffered
+                let buf = null::to_binary(value).map_err(|e| {
+                    fail(plist_err_t::PLIST_ERR_UNKNOWN, format_args!("binary: {e}"))
+                })?;
+                return out.write_all(&buf).map_err(io_err);
+            }
+            // Not text, so there's no newline to leave out
+            plist::to_writer_binary(out, value).map_err(|e| plist_err("binary", e))
+        }
+        PlistFormat::PLIST_FORMAT_JSON => {
+            let json = json::Json(value);
+            if compact {
+                serde_json::to_writer(&mut *out, &json)
+            } else {
+                serde_json::to_writer_pretty(&mut *out, &json)
+            }
+            .map_err(|e| {
+                if e.is_io() {
+                    io_err(e.into())
+                } else {
+                    fail(plist_err_t::PLIST_ERR_FORMAT, format_args!("JSON: {e}"))
+                }
+            })?;
+            if compact {
+                return Ok(());
+            }
+            out.write_all(newline).map_err(io_err)
+        }
+        PlistFormat::PLIST_FORMAT_OSTEP => {
+            write_text(out, &openstep::to_openstep(value, !compact)?, options)
+        }
+        PlistFormat::PLIST_FORMAT_PRINT => {
+            write_text(out, print::to_print(value, options).as_bytes(), options)
+        }
+        PlistFormat::PLIST_FORMAT_LIMD => {
+            write_text(out, print::to_limd(value, options).as_bytes(), options)
+        }
+        PlistFormat::PLIST_FORMAT_PLUTIL => {
+            write_text(out, print::to_plutil(value).as_bytes(), options)
+        }
+        _ => Err(fail(
+            plist_err_t::PLIST_ERR_INVALID_ARG,
+            "unsupported output format",
+        )),
+    }
+}
+
+/// Writes out a document that was put together in memory,
+/// leaving off the final newline if PLIST_OPT_NO_NEWLINE says so
+fn write_text(
+    out: &mut impl Write,
+    text: &[u8],
+    options: PlistWriteOptions,
+) -> Result<(), plist_err_t> {
+    let text = match text.strip_suffix(b"\n") {
+        Some(stripped) if options & PLIST_OPT_NO_NEWLINE != 0 => stripped,
+        _ => text,
+    };
+    out.write_all(text).map_err(io_err)
+}
+
+fn io_err(e: std::io::Error) -> plist_err_t {
+    fail(plist_err_t::PLIST_ERR_IO, format_args!("write failed: {e}"))
+}
+
+/// Parses a document in the given format, describing what went wrong on failure
+fn parse(data: &[u8], format: PlistFormat) -> Result<Value, String> {
+    // The XML reader skips a BOM itself, the JSON and OpenStep parsers don't
+    let text = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+    match format {
+        PlistFormat::PLIST_FORMAT_BINARY => {
+            if !data.starts_with(BINARY_MAGIC) {
+                return Err("binary: missing bplist00 header".to_string());
+            }
+            null::from_binary(data).map_err(|e| format!("binary: {e}"))
+        }
+        PlistFormat::PLIST_FORMAT_XML => {
+            Value::from_reader_xml(data).map_err(|e| format!("XML: {e}"))
+        }
+        PlistFormat::PLIST_FORMAT_JSON => serde_json::from_slice(text)
+            .map(json::from_json)
+            .map_err(|e| format!("JSON: {e}")),
+        PlistFormat::PLIST_FORMAT_OSTEP => {
+            openstep::from_openstep(text).map_err(|e| format!("OpenStep: {e}"))
+        }
+        _ => Err("unsupported input format".to_string()),
+    }
+}
+
+/// Parses a document in the given format and hands it over to the caller
+unsafe fn parse_into(data: &[u8], format: PlistFormat, plist: *mut plist_t) -> plist_err_t {
+    match parse(data, format) {
+        Ok(value) => unsafe { hand_off_node(value, plist) },
+        Err(e) => fail(plist_err_t::PLIST_ERR_PARSE, e),
+    }
+}
+
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn plist_to_bin(
+pub unsafe extern "C" fn plist_to_xml(
     node: plist_t,
-    plist_bin: *mut *mut c_char,
+    plist_xml: *mut *mut c_char,
     length: *mut u32,
 ) -> plist_err_t {
-    let node = unsafe { &mut *node }.borrow_self();
-
-    let buf = Vec::new();
-    let mut writer = std::io::BufWriter::new(buf);
-    plist::to_writer_binary(&mut writer, node).unwrap();
-    let mut xml = writer.into_inner().unwrap();
-    xml.push(0);
-    let mut boxed = xml.into_boxed_slice();
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = 0;
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_XML,
+                options,
+                plist_xml,
+                length,
+            )
+        }
+    })
+}
 
-    let ptr = boxed.as_mut_ptr();
+/// plist_to_xml with a 64-bit length
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_to_xml64(
+    node: plist_t,
+    plist_xml: *mut *mut c_char,
+    length: *mut u64,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = 0;
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_XML,
+                options,
+                plist_xml,
+                length,
+            )
+        }
+    })
+}
 
-    // Return original length (excluding null terminator)
-    unsafe {
-        *plist_bin = ptr as *mut c_char;
-        *length = (boxed.len() - 1) as u32;
-    }
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_to_bin(
+    node: plist_t,
+    plist_bin: *mut *mut c_char,
+    length: *mut u32,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = 0;
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_BINARY,
+                options,
+                plist_bin,
+                length,
+            )
+        }
+    })
+}
 
-    // Prevent Rust from freeing it - caller must free
-    std::mem::forget(boxed);
-    plist_err_t::PLIST_ERR_SUCCESS
+/// plist_to_bin with a 64-bit length
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_to_bin64(
+    node: plist_t,
+    plist_bin: *mut *mut c_char,
+    length: *mut u64,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = 0;
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_BINARY,
+                options,
+                plist_bin,
+                length,
+            )
+        }
+    })
 }
 
 /// # Safety
@@ -75,46 +339,99 @@ pub unsafe extern "C" fn plist_to_json(
     length: *mut u32,
     prettify: i32,
 ) -> plist_err_t {
-    let node = unsafe { &mut *node }.borrow_self();
-
-    let mut s = if prettify > 0 {
-        serde_json::to_string_pretty(node)
-    } else {
-        serde_json::to_string(node)
-    }
-    .unwrap()
-    .as_bytes()
-    .to_vec();
-
-    s.push(0);
-    let mut boxed = s.into_boxed_slice();
-
-    let ptr = boxed.as_mut_ptr();
-
-    // Return original length (excluding null terminator)
-    unsafe {
-        *plist_json = ptr as *mut c_char;
-        *length = (boxed.len() - 1) as u32;
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = if prettify > 0 { 0 } else { PLIST_OPT_COMPACT };
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_JSON,
+                options,
+                plist_json,
+                length,
+            )
+        }
+    })
+}
 
-    // Prevent Rust from freeing it - caller must free
-    std::mem::forget(boxed);
-    plist_err_t::PLIST_ERR_SUCCESS
+/// plist_to_json with a 64-bit length
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_to_json64(
+    node: plist_t,
+    plist_json: *mut *mut c_char,
+    length: *mut u64,
+    prettify: i32,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = if prettify > 0 { 0 } else { PLIST_OPT_COMPACT };
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_JSON,
+                options,
+                plist_json,
+                length,
+            )
+        }
+    })
 }
 
-/// There is hardly any information on this format. Hopefully this isn't used anywhere.
+/// Converts the plist into the OpenStep format, the way libplist does.
+/// Booleans, dates and UIDs can't be represented and return PLIST_ERR_FORMAT.
 /// # Safety
-/// don't use this
+/// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_to_openstep(
-    _node: plist_t,
-    _plist_json: *mut *mut c_char,
-    _length: *mut u32,
-    _prettify: i32,
+    node: plist_t,
+    plist_openstep: *mut *mut c_char,
+    length: *mut u32,
+    prettify: i32,
 ) -> plist_err_t {
-    unimplemented!()
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = if prettify > 0 { 0 } else { PLIST_OPT_COMPACT };
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_OSTEP,
+                options,
+                plist_openstep,
+                length,
+            )
+        }
+    })
 }
 
+/// plist_to_openstep with a 64-bit length
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_to_openstep64(
+    node: plist_t,
+    plist_openstep: *mut *mut c_char,
+    length: *mut u64,
+    prettify: i32,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let options = if prettify > 0 { 0 } else { PLIST_OPT_COMPACT };
+        unsafe {
+            write_to_buffer(
+                node,
+                PlistFormat::PLIST_FORMAT_OSTEP,
+                options,
+                plist_openstep,
+                length,
+            )
+        }
+    })
+}
+
+/// Every binary plist starts with this
+const BINARY_MAGIC: &[u8] = b"bplist00";
+
+/// Editors like to start UTF-8 text with this
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
 /// # Safety
 /// Don't be stupid
 #[unsafe(no_mangle)]
@@ -123,14 +440,24 @@ pub unsafe extern "C" fn plist_from_xml(
     length: u32,
     plist: *mut plist_t,
 ) -> plist_err_t {
-    let data = unsafe { std::slice::from_raw_parts(plist_xml as *const u8, length as usize) };
-    if let Ok(data) = plist::from_bytes(data) {
-        let p = PlistWrapper::new_node(data).into_ptr();
-        unsafe { *plist = p };
-        plist_err_t::PLIST_ERR_SUCCESS
-    } else {
-        plist_err_t::PLIST_ERR_PARSE
-    }
+    unsafe { plist_from_xml64(plist_xml, length.into(), plist) }
+}
+
+/// plist_from_xml with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_xml64(
+    plist_xml: *const c_char,
+    length: u64,
+    plist: *mut plist_t,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        match unsafe { borrow_input(plist_xml, length) } {
+            Ok(data) => unsafe { parse_into(data, PlistFormat::PLIST_FORMAT_XML, plist) },
+            Err(e) => e,
+        }
+    })
 }
 
 /// # Safety
@@ -141,14 +468,24 @@ pub unsafe extern "C" fn plist_from_bin(
     length: u32,
     plist: *mut plist_t,
 ) -> plist_err_t {
-    let data = unsafe { std::slice::from_raw_parts(plist_bin as *const u8, length as usize) };
-    if let Ok(data) = plist::from_bytes(data) {
-        let p = PlistWrapper::new_node(data).into_ptr();
-        unsafe { *plist = p };
-        plist_err_t::PLIST_ERR_SUCCESS
-    } else {
-        plist_err_t::PLIST_ERR_PARSE
-    }
+    unsafe { plist_from_bin64(plist_bin, length.into(), plist) }
+}
+
+/// plist_from_bin with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_bin64(
+    plist_bin: *const c_char,
+    length: u64,
+    plist: *mut plist_t,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        match unsafe { borrow_input(plist_bin, length) } {
+            Ok(data) => unsafe { parse_into(data, PlistFormat::PLIST_FORMAT_BINARY, plist) },
+            Err(e) => e,
+        }
+    })
 }
 
 /// # Safety
@@ -159,16 +496,56 @@ pub unsafe extern "C" fn plist_from_json(
     length: u32,
     plist: *mut plist_t,
 ) -> plist_err_t {
-    let data = unsafe { std::slice::from_raw_parts(plist_json as *const u8, length as usize) };
-    if let Ok(data) = serde_json::from_slice(data) {
-        let p = PlistWrapper::new_node(data).into_ptr();
-        unsafe { *plist = p };
-        plist_err_t::PLIST_ERR_SUCCESS
-    } else {
-        plist_err_t::PLIST_ERR_PARSE
-    }
+    unsafe { plist_from_json64(plist_json, length.into(), plist) }
 }
 
+/// plist_from_json with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_json64(
+    plist_json: *const c_char,
+    length: u64,
+    plist: *mut plist_t,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        match unsafe { borrow_input(plist_json, length) } {
+            Ok(data) => unsafe { parse_into(data, PlistFormat::PLIST_FORMAT_JSON, plist) },
+            Err(e) => e,
+        }
+    })
+}
+
+/// Parses an OpenStep plist, including `.strings` files
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_openstep(
+    plist_ostep: *const c_char,
+    length: u32,
+    plist: *mut plist_t,
+) -> plist_err_t {
+    unsafe { plist_from_openstep64(plist_ostep, length.into(), plist) }
+}
+
+/// plist_from_openstep with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_openstep64(
+    plist_ostep: *const c_char,
+    length: u64,
+    plist: *mut plist_t,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        match unsafe { borrow_input(plist_ostep, length) } {
+            Ok(data) => unsafe { parse_into(data, PlistFormat::PLIST_FORMAT_OSTEP, plist) },
+            Err(e) => e,
+        }
+    })
+}
+
+/// Parses a plist in any supported format, reporting the format that was found
 /// # Safety
 /// Don't be stupid
 #[unsafe(no_mangle)]
@@ -178,20 +555,88 @@ pub unsafe extern "C" fn plist_from_memory(
     plist: *mut plist_t,
     plist_format: *mut PlistFormat,
 ) -> plist_err_t {
-    unsafe {
-        if plist_from_xml(plist_data, length, plist) == plist_err_t::PLIST_ERR_SUCCESS {
-            if !plist_format.is_null() {
-                *plist_format = PlistFormat::PLIST_FORMAT_XML; // this can also be true for binary
-            }
-            return plist_err_t::PLIST_ERR_SUCCESS;
+    unsafe { plist_from_memory64(plist_data, length.into(), plist, plist_format) }
+}
+
+/// plist_from_memory with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_from_memory64(
+    plist_data: *const c_char,
+    length: u64,
+    plist: *mut plist_t,
+    plist_format: *mut PlistFormat,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        match unsafe { borrow_input(plist_data, length) } {
+            Ok(data) => unsafe { parse_any_into(data, plist, plist_format) },
+            Err(e) => e,
         }
-        if plist_from_json(plist_data, length, plist) == plist_err_t::PLIST_ERR_SUCCESS {
-            if !plist_format.is_null() {
-                *plist_format = PlistFormat::PLIST_FORMAT_JSON;
+    })
+}
+
+/// Parses a document in whichever format it turns out to be in
+/// and hands it over to the caller, along with the format
+unsafe fn parse_any_into(
+    data: &[u8],
+    plist: *mut plist_t,
+    plist_format: *mut PlistFormat,
+) -> plist_err_t {
+    // Ambiguous documents report why every candidate format failed
+    let mut errors = Vec::new();
+    for format in candidate_formats(data) {
+        match parse(data, *format) {
+            Ok(value) => {
+                let res = unsafe { hand_off_node(value, plist) };
+                if res == plist_err_t::PLIST_ERR_SUCCESS && !plist_format.is_null() {
+                    unsafe { *plist_format = *format };
+                }
+                return res;
+            }
+            Err(e) => {
+                errors.push(e);
             }
-            return plist_err_t::PLIST_ERR_SUCCESS;
         }
-        plist_err_t::PLIST_ERR_PARSE
+    }
+    fail(
+        plist_err_t::PLIST_ERR_PARSE,
+        if errors.is_empty() {
+            "empty document".to_string()
+        } else {
+            errors.join("; ")
+        },
+    )
+}
+
+/// Sniffs the leading bytes of a document and returns the formats
+/// it could be in, most likely first.
+fn candidate_formats(data: &[u8]) -> &'static [PlistFormat] {
+    if data.starts_with(BINARY_MAGIC) {
+        return &[PlistFormat::PLIST_FORMAT_BINARY];
+    }
+
+    let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+    let Some(start) = data.iter().position(|b| !b.is_ascii_whitespace()) else {
+        return &[];
+    };
+    let data = &data[start..];
+
+    match data[0] {
+        // <?xml, <!DOCTYPE or a bare <plist, anything else is OpenStep data
+        b'<' if matches!(data.get(1), Some(b'?' | b'!' | b'p')) => &[PlistFormat::PLIST_FORMAT_XML],
+        b'[' => &[PlistFormat::PLIST_FORMAT_JSON],
+        b'(' | b'<' => &[PlistFormat::PLIST_FORMAT_OSTEP],
+        // Valid for both, JSON is stricter so give it the first shot
+        b'{' | b'"' | b'-' | b'0'..=b'9' => &[
+            PlistFormat::PLIST_FORMAT_JSON,
+            PlistFormat::PLIST_FORMAT_OSTEP,
+        ],
+        _ if data.starts_with(b"true") || data.starts_with(b"false") => &[
+            PlistFormat::PLIST_FORMAT_JSON,
+            PlistFormat::PLIST_FORMAT_OSTEP,
+        ],
+        _ => &[PlistFormat::PLIST_FORMAT_OSTEP],
     }
 }
 
@@ -203,20 +648,69 @@ pub unsafe extern "C" fn plist_read_from_file(
     plist: *mut plist_t,
     plist_format: *mut PlistFormat,
 ) -> plist_err_t {
-    let filename = unsafe { CStr::from_ptr(filename) }.to_str().unwrap();
-    let f = match std::fs::read(filename) {
-        Ok(f) => f,
-        Err(_) => return plist_err_t::PLIST_ERR_IO,
-    };
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let Some(filename) = (unsafe { borrow_str(filename) }) else {
+            return fail(
+                plist_err_t::PLIST_ERR_INVALID_ARG,
+                "filename is null or not UTF-8",
+            );
+        };
+        let f = match std::fs::read(filename) {
+            Ok(f) => f,
+            Err(e) => return fail(plist_err_t::PLIST_ERR_IO, format_args!("{filename}: {e}")),
+        };
+
+        unsafe { parse_any_into(&f, plist, plist_format) }
+    })
+}
 
-    unsafe {
-        plist_from_memory(
-            f.as_ptr() as *const c_char,
-            f.len() as u32,
-            plist,
-            plist_format,
-        )
+/// Reads a plist in any supported format from the stream, until it ends,
+/// reporting the format that was found
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_read_from_stream(
+    stream: *mut libc::FILE,
+    plist: *mut plist_t,
+    plist_format: *mut PlistFormat,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        if stream.is_null() {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "stream is null");
+        }
+        unsafe { read_any_into(FileStream(stream), plist, plist_format) }
+    })
+}
+
+/// Reads a plist in any supported format from a file descriptor, until it ends,
+/// reporting the format that was found.
+/// The descriptor is left open.
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_read_from_fd(
+    fd: c_int,
+    plist: *mut plist_t,
+    plist_format: *mut PlistFormat,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        if fd < 0 {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "fd is negative");
+        }
+        unsafe { read_any_into(Fd(fd), plist, plist_format) }
+    })
+}
+
+unsafe fn read_any_into(
+    mut input: impl Read,
+    plist: *mut plist_t,
+    plist_format: *mut PlistFormat,
+) -> plist_err_t {
+    let mut data = Vec::new();
+    if let Err(e) = input.read_to_end(&mut data) {
+        return fail(plist_err_t::PLIST_ERR_IO, format_args!("read failed: {e}"));
     }
+    unsafe { parse_any_into(&data, plist, plist_format) }
 }
 
 /// # Safety
@@ -229,46 +723,28 @@ pub unsafe extern "C" fn plist_write_to_string(
     format: PlistFormat,
     options: PlistWriteOptions,
 ) -> plist_err_t {
-    let node = unsafe { &mut *plist }.borrow_self();
-
-    let mut data = match format {
-        PlistFormat::PLIST_FORMAT_XML => {
-            let buf = Vec::new();
-            let mut writer = std::io::BufWriter::new(buf);
-            plist::to_writer_xml(&mut writer, node).unwrap();
-            writer.into_inner().unwrap()
-        }
-        PlistFormat::PLIST_FORMAT_BINARY => {
-            let buf = Vec::new();
-            let mut writer = std::io::BufWriter::new(buf);
-            plist::to_writer_binary(&mut writer, node).unwrap();
-            writer.into_inner().unwrap()
-        }
-        PlistFormat::PLIST_FORMAT_JSON => {
-            if options & PLIST_OPT_INDENT != 0 {
-                serde_json::to_vec_pretty(node).unwrap()
-            } else {
-                serde_json::to_vec(node).unwrap()
-            }
-        }
-        _ => return plist_err_t::PLIST_ERR_INVALID_ARG,
-    };
-    data.push(0);
-    let mut boxed = data.into_boxed_slice();
-
-    let ptr = boxed.as_mut_ptr();
-
-    // Return original length (excluding null terminator)
-    unsafe {
-        *output = ptr as *mut c_char;
-        *length = (boxed.len() - 1) as u32;
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        write_to_buffer(plist, format, options, output, length)
+    })
+}
 
-    // Prevent Rust from freeing it - caller must free
-    std::mem::forget(boxed);
-    plist_err_t::PLIST_ERR_SUCCESS
+/// plist_write_to_string with a 64-bit length
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_write_to_string64(
+    plist: plist_t,
+    output: *mut *mut c_char,
+    length: *mut u64,
+    format: PlistFormat,
+    options: PlistWriteOptions,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || unsafe {
+        write_to_buffer(plist, format, options, output, length)
+    })
 }
 
+/// Writes the plist to the stream as it's serialized, then flushes it
 /// # Safety
 /// Don't be stupid
 #[unsafe(no_mangle)]
@@ -278,46 +754,130 @@ pub unsafe extern "C" fn plist_write_to_stream(
     format: PlistFormat,
     options: PlistWriteOptions,
 ) -> plist_err_t {
-    if plist.is_null() || stream.is_null() {
-        return plist_err_t::PLIST_ERR_INVALID_ARG;
-    }
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        if stream.is_null() {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "stream is null");
+        }
+        let Some(value) = (unsafe { borrow_value(plist) }) else {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        };
+
+        let mut out = FileStream(stream);
+        match serialize_to(value, format, options, &mut out)
+            .and_then(|()| out.flush().map_err(io_err))
+        {
+            Ok(()) => plist_err_t::PLIST_ERR_SUCCESS,
+            Err(e) => e,
+        }
+    })
+}
+
+/// Writes the plist to a file descriptor as it's serialized.
+/// The descriptor is left open.
+/// # Safety
+/// Don't be stupid
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_write_to_fd(
+    plist: plist_t,
+    fd: c_int,
+    format: PlistFormat,
+    options: PlistWriteOptions,
+) -> plist_err_t {
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        if fd < 0 {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "fd is negative");
+        }
+        let Some(value) = (unsafe { borrow_value(plist) }) else {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        };
+
+        let mut out = BufWriter::new(Fd(fd));
+        match serialize_to(value, format, options, &mut out)
+            .and_then(|()| out.flush().map_err(io_err))
+        {
+            Ok(()) => plist_err_t::PLIST_ERR_SUCCESS,
+            Err(e) => e,
+        }
+    })
+}
 
-    let wrapper = unsafe { &mut *plist };
-    let value = wrapper.borrow_self();
+/// A caller's FILE*, which does its own buffering
+struct FileStream(*mut libc::FILE);
 
-    let mut buf = Vec::new();
-    let result = match format {
-        PlistFormat::PLIST_FORMAT_JSON => {
-            buf = if options & PLIST_OPT_INDENT != 0 {
-                serde_json::to_vec_pretty(value)
-            } else {
-                serde_json::to_vec(value)
-            }
-            .unwrap();
-            Ok(())
+impl Read for FileStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = unsafe { libc::fread(buf.as_mut_ptr() as *mut c_void, 1, buf.len(), self.0) };
+        if read == 0 && unsafe { libc::ferror(self.0) } != 0 {
+            return Err(std::io::Error::last_os_error());
         }
-        PlistFormat::PLIST_FORMAT_XML => plist::to_writer_xml(&mut buf, value),
-        PlistFormat::PLIST_FORMAT_BINARY => plist::to_writer_binary(&mut buf, value),
-        _ => return plist_err_t::PLIST_ERR_INVALID_ARG,
-    };
+        Ok(read)
+    }
+}
 
-    if result.is_err() {
-        return plist_err_t::PLIST_ERR_INVALID_ARG;
+impl Write for FileStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = unsafe { libc::fwrite(buf.as_ptr() as *const c_void, 1, buf.len(), self.0) };
+        if written < buf.len() && unsafe { libc::ferror(self.0) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(written)
     }
 
-    if unsafe { !write_to_stream(stream, &buf) } {
-        return plist_err_t::PLIST_ERR_INVALID_ARG;
+    fn flush(&mut self) -> std::io::Result<()> {
+        if unsafe { libc::fflush(self.0) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
     }
+}
 
-    unsafe { libc::fflush(stream) };
-    plist_err_t::PLIST_ERR_SUCCESS
+/// The C library's stdout, which Rust's own stdout buffers separately from
+fn c_stdout() -> *mut libc::FILE {
+    #[cfg(windows)]
+    {
+        unsafe extern "C" {
+            fn __acrt_iob_func(index: libc::c_uint) -> *mut libc::FILE;
+        }
+        unsafe { __acrt_iob_func(1) }
+    }
+    #[cfg(not(windows))]
+    {
+        unsafe extern "C" {
+            #[cfg_attr(target_vendor = "apple", link_name = "__stdoutp")]
+            static stdout: *mut libc::FILE;
+        }
+        unsafe { stdout }
+    }
 }
 
-unsafe fn write_to_stream(stream: *mut libc::FILE, buf: &[u8]) -> bool {
-    let written = unsafe { libc::fwrite(buf.as_ptr() as *const c_void, 1, buf.len(), stream) };
-    written == buf.len()
+/// A caller's file descriptor, not closed when dropped
+struct Fd(c_int);
+
+impl Read for Fd {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = unsafe { libc::read(self.0, buf.as_mut_ptr() as *mut c_void, buf.len() as _) };
+        if read < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(read as usize)
+    }
 }
 
+impl Write for Fd {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = unsafe { libc::write(self.0, buf.as_ptr() as *const c_void, buf.len() as _) };
+        if written < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the plist to the file as it's serialized
 /// # Safety
 /// Don't be stupid
 #[unsafe(no_mangle)]
@@ -327,146 +887,67 @@ pub unsafe extern "C" fn plist_write_to_file(
     format: PlistFormat,
     options: PlistWriteOptions,
 ) -> plist_err_t {
-    let value = unsafe { &mut *plist }.borrow_self();
-    let filename = unsafe { CStr::from_ptr(filename) }.to_str().unwrap();
-    let mut buf = Vec::new();
-    let result = match format {
-        PlistFormat::PLIST_FORMAT_JSON => {
-            buf = if options & PLIST_OPT_INDENT != 0 {
-                serde_json::to_vec_pretty(value)
-            } else {
-                serde_json::to_vec(value)
-            }
-            .unwrap();
-            Ok(())
+    ffi_guard(plist_err_t::PLIST_ERR_UNKNOWN, || {
+        let Some(value) = (unsafe { borrow_value(plist) }) else {
+            return fail(plist_err_t::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        };
+        let Some(filename) = (unsafe { borrow_str(filename) }) else {
+            return fail(
+                plist_err_t::PLIST_ERR_INVALID_ARG,
+                "filename is null or not UTF-8",
+            );
+        };
+        let file = match std::fs::File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return fail(plist_err_t::PLIST_ERR_IO, format_args!("{filename}: {e}")),
+        };
+
+        let mut out = BufWriter::new(file);
+        match serialize_to(value, format, options, &mut out)
+            .and_then(|()| out.flush().map_err(io_err))
+        {
+            Ok(()) => plist_err_t::PLIST_ERR_SUCCESS,
+            Err(e) => e,
         }
-        PlistFormat::PLIST_FORMAT_XML => plist::to_writer_xml(&mut buf, value),
-        PlistFormat::PLIST_FORMAT_BINARY => plist::to_writer_binary(&mut buf, value),
-        _ => return plist_err_t::PLIST_ERR_INVALID_ARG,
-    };
-    if result.is_err() {
-        return plist_err_t::PLIST_ERR_INVALID_ARG;
-    }
-
-    if std::fs::write(filename, buf).is_ok() {
-        plist_err_t::PLIST_ERR_SUCCESS
-    } else {
-        plist_err_t::PLIST_ERR_IO
-    }
+    })
 }
 
 /// # Safety
 /// Pass a valid plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_print(plist: plist_t) {
-    let node = unsafe { &mut *plist }.borrow_self();
-    println!("{}", pretty_print_plist(node));
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(plist) } {
+            // Through C's stdout, so it lands in order with the caller's printf
+            let _ = serialize_to(
+                node,
+                PlistFormat::PLIST_FORMAT_PRINT,
+                PLIST_OPT_PARTIAL_DATA,
+                &mut FileStream(c_stdout()),
+            );
+        }
+    })
 }
 
 /// # Safety
 /// Pass a valid plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_is_binary(plist_data: *const c_char, length: u32) -> u8 {
-    let data = unsafe { std::slice::from_raw_parts(plist_data as *const u8, length as usize) };
-    if data.is_ascii() { 0 } else { 1 }
-}
-
-/////////////////////////
-// STOLEN FROM IDEVICE //
-/////////////////////////
-
-/// Pretty-prints a PLIST value with indentation
-///
-/// # Arguments
-/// * `p` - The PLIST value to format
-///
-/// # Returns
-/// A formatted string representation
-pub fn pretty_print_plist(p: &Value) -> String {
-    print_plist(p, 0)
-}
-
-/// Pretty-prints a PLIST dictionary with key-value pairs
-///
-/// # Arguments
-/// * `dict` - The dictionary to format
-///
-/// # Returns
-/// A formatted string representation with newlines and indentation
-///
-/// # Example
-/// ```rust
-/// let mut dict = plist::Dictionary::new();
-/// dict.insert("name".into(), "John".into());
-/// dict.insert("age".into(), 30.into());
-/// println!("{}", pretty_print_dictionary(&dict));
-/// ```
-pub fn pretty_print_dictionary(dict: &plist::Dictionary) -> String {
-    let items: Vec<String> = dict
-        .iter()
-        .map(|(k, v)| format!("{}: {}", k, print_plist(v, 2)))
-        .collect();
-    format!("{{\n{}\n}}", items.join(",\n"))
-}
-
-/// Internal recursive function for printing PLIST values with indentation
-///
-/// # Arguments
-/// * `p` - The PLIST value to format
-/// * `indentation` - Current indentation level
-///
-/// # Returns
-/// Formatted string representation
-fn print_plist(p: &Value, indentation: usize) -> String {
-    let indent = " ".repeat(indentation);
-    match p {
-        Value::Array(vec) => {
-            let items: Vec<String> = vec
-                .iter()
-                .map(|v| {
-                    format!(
-                        "{}{}",
-                        " ".repeat(indentation + 2),
-                        print_plist(v, indentation + 2)
-                    )
-                })
-                .collect();
-            format!("[\n{}\n{}]", items.join(",\n"), indent)
-        }
-        Value::Dictionary(dict) => {
-            let items: Vec<String> = dict
-                .iter()
-                .map(|(k, v)| {
-                    format!(
-                        "{}{}: {}",
-                        " ".repeat(indentation + 2),
-                        k,
-                        print_plist(v, indentation + 2)
-                    )
-                })
-                .collect();
-            format!("{{\n{}\n{}}}", items.join(",\n"), indent)
-        }
-        Value::Boolean(b) => format!("{b}"),
-        Value::Data(vec) => {
-            let len = vec.len();
-            let preview: String = vec
-                .iter()
-                .take(20)
-                .map(|b| format!("{b:02X}"))
-                .collect::<Vec<String>>()
-                .join(" ");
-            if len > 20 {
-                format!("Data({preview}... Len: {len})")
-            } else {
-                format!("Data({preview} Len: {len})")
-            }
+    unsafe { plist_is_binary64(plist_data, length.into()) }
+}
+
+/// plist_is_binary with a 64-bit length
+/// # Safety
+/// Pass a valid plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_is_binary64(plist_data: *const c_char, length: u64) -> u8 {
+    ffi_guard(0, || {
+        let Ok(length) = usize::try_from(length) else {
+            return 0;
+        };
+        match unsafe { borrow_bytes(plist_data as *const u8, length) } {
+            Some(data) if data.starts_with(BINARY_MAGIC) => 1,
+            _ => 0,
         }
-        Value::Date(date) => format!("Date({})", date.to_xml_format()),
-        Value::Real(f) => format!("{f}"),
-        Value::Integer(i) => format!("{i}"),
-        Value::String(s) => format!("\"{s}\""),
-        Value::Uid(_uid) => "Uid(?)".to_string(),
-        _ => "Unknown".to_string(),
-    }
+    })
 }
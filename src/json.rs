@@ -0,0 +1,57 @@
+// Jackson Coxson
+// Conversions between plist values and JSON, mapping JSON null to the null node
+// so it survives the trip.
+
+use plist::Value;
+use serde::{Serialize, Serializer};
+use serde_json::Value as JsonValue;
+
+use crate::null;
+
+/// Borrows a plist value to write it as JSON, without building a JSON copy first.
+/// Data becomes an array of bytes, dates become ISO 8601 strings and UIDs become numbers.
+pub(crate) struct Json<'a>(pub(crate) &'a Value);
+
+impl Serialize for Json<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            v if null::is_null(v) => s.serialize_unit(),
+            Value::Array(a) => s.collect_seq(a.iter().map(Json)),
+            Value::Dictionary(d) => s.collect_map(d.iter().map(|(k, v)| (k, Json(v)))),
+            Value::Boolean(b) => s.serialize_bool(*b),
+            Value::Data(d) => s.collect_seq(d),
+            Value::Date(d) => s.serialize_str(&d.to_xml_format()),
+            Value::Real(r) if r.is_finite() => s.serialize_f64(*r),
+            Value::Integer(i) => match (i.as_signed(), i.as_unsigned()) {
+                (Some(i), _) => s.serialize_i64(i),
+                (_, Some(u)) => s.serialize_u64(u),
+                _ => s.serialize_unit(),
+            },
+            Value::String(string) => s.serialize_str(string),
+            Value::Uid(u) => s.serialize_u64(u.get()),
+            _ => s.serialize_unit(),
+        }
+    }
+}
+
+/// Converts JSON into a plist value
+pub(crate) fn from_json(value: JsonValue) -> Value {
+    match value {
+        JsonValue::Null => null::null(),
+        JsonValue::Bool(b) => Value::Boolean(b),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::Integer(i.into())
+            } else if let Some(u) = n.as_u64() {
+                Value::Integer(u.into())
+            } else {
+                Value::Real(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        JsonValue::String(s) => Value::String(s),
+        JsonValue::Array(a) => Value::Array(a.into_iter().map(from_json).collect()),
+        JsonValue::Object(o) => {
+            Value::Dictionary(o.into_iter().map(|(k, v)| (k, from_json(v))).collect())
+        }
+    }
+}
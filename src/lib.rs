@@ -1,14 +1,31 @@
 // Jackson Coxson
 
+use std::{
+    collections::HashMap,
+    ffi::{CStr, c_char},
+    panic::AssertUnwindSafe,
+};
+
 use plist::Value;
 
 pub mod array;
 pub mod creation;
+pub mod date;
 pub mod dict;
+pub mod diff;
+pub mod error;
 pub mod getters;
+pub mod handles;
 pub mod import;
+pub mod json;
+pub mod null;
+pub mod openstep;
+pub mod patch;
+pub mod path;
+pub mod print;
 pub mod setters;
 pub mod utils;
+pub mod xml;
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
@@ -42,6 +59,7 @@ pub enum PlistErr {
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum PlistFormat {
     PLIST_FORMAT_NONE = 0,
     PLIST_FORMAT_XML = 1,
@@ -53,11 +71,43 @@ pub enum PlistFormat {
     PLIST_FORMAT_PLUTIL = 12,
 }
 
+/// How plist_dict_merge_deep settles a key both dictionaries have.
+/// Dictionaries on both sides are always merged recursively.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlistMergeStrategy {
+    /// Keep the target's value
+    PLIST_MERGE_KEEP = 0,
+    /// Replace the target's value with the source's
+    PLIST_MERGE_OVERWRITE = 1,
+    /// Append the source's array to the target's, overwrite anything else
+    PLIST_MERGE_CONCAT_ARRAYS = 2,
+    /// Ask the callback, which returns one of the strategies above
+    PLIST_MERGE_CALLBACK = 3,
+}
+
+impl TryFrom<u32> for PlistMergeStrategy {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::PLIST_MERGE_KEEP,
+            1 => Self::PLIST_MERGE_OVERWRITE,
+            2 => Self::PLIST_MERGE_CONCAT_ARRAYS,
+            3 => Self::PLIST_MERGE_CALLBACK,
+            v => return Err(format!("{v} isn't a merge strategy")),
+        })
+    }
+}
+
 pub type PlistWriteOptions = u32;
 pub const PLIST_OPT_NONE: PlistWriteOptions = 0;
 pub const PLIST_OPT_COMPACT: PlistWriteOptions = 1 << 0;
 pub const PLIST_OPT_PARTIAL_DATA: PlistWriteOptions = 1 << 1;
 pub const PLIST_OPT_NO_NEWLINE: PlistWriteOptions = 1 << 2;
+/// Shifts PRINT and LIMD output right, by as many levels as the top byte says,
+/// `PLIST_OPT_INDENT | (levels << 24)`. Like libplist, PLUTIL ignores it.
 pub const PLIST_OPT_INDENT: PlistWriteOptions = 1 << 3;
 
 #[allow(non_camel_case_types)]
@@ -71,30 +121,170 @@ type plist_err_t = PlistErr;
 
 pub struct PlistWrapper {
     node: NodeType,
-    children_wrappers: Vec<*mut PlistWrapper>,
+    /// The wrappers handed out for parts of this one, by where they sit,
+    /// so looking one up again doesn't scan them all
+    children_wrappers: HashMap<Slot, *mut PlistWrapper>,
+}
+
+/// Where a child wrapper sits in the wrapper owning it
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Slot {
+    Index(u32),
+    Key(String),
+    /// The key node of the dictionary item the owner is
+    KeyNode,
 }
 
 pub enum NodeType {
     Node(Value),
+    /// An element of a container, looked up through the parent every time
+    /// so it keeps up with inserts and reallocations
     Child {
-        node: *mut Value,
-        parent: *mut Value,
+        parent: *mut PlistWrapper,
         index: u32,          // for arrays
         key: Option<String>, // for dictionaries
     },
     Iterator(u32),
+    /// A dictionary key, which the plist crate has no value for
+    Key(String),
 }
 
 /// An FFI, libplist, compatible wrapper for plist's Value.
 impl PlistWrapper {
+    /// Borrows the wrapper behind a handle.
+    /// Returns None if the handle is null, or isn't a live handle from this library,
+    /// in which case it's reported through the last error.
+    /// # Safety
+    /// Don't hold on to the borrow past the wrapper being freed
+    pub(crate) unsafe fn from_handle<'a>(handle: plist_t) -> Option<&'a mut PlistWrapper> {
+        if handle.is_null() {
+            return None;
+        }
+        if !handles::is_live(handle) {
+            error::set_last_error("invalid plist handle, it was freed or never allocated");
+            return None;
+        }
+        unsafe { handle.as_mut() }
+    }
     /// Gets a reference to the Value from the wrapper
     /// Note that you cannot retrieve the actual value,
     /// as the value might be a child of another wrapper.
-    pub fn borrow_self(&mut self) -> &mut Value {
+    /// Returns None for iterators and keys, which don't hold a value.
+    pub fn borrow_self(&mut self) -> Option<&mut Value> {
+        match &mut self.node {
+            NodeType::Node(value) => Some(value),
+            NodeType::Child { parent, index, key } => unsafe {
+                resolve_child(*parent, *index, key)
+            },
+            NodeType::Iterator(_) | NodeType::Key(_) => None,
+        }
+    }
+    /// Frees the child wrappers of elements that went away, their handles turn invalid.
+    /// `gone` gets the index and key of each child.
+    pub(crate) fn release_children(&mut self, gone: impl Fn(u32, Option<&str>) -> bool) {
+        let released: Vec<Slot> = self
+            .children_wrappers
+            .keys()
+            .filter(|slot| match slot {
+                Slot::Index(index) => gone(*index, None),
+                Slot::Key(key) => gone(u32::MAX, Some(key)),
+                Slot::KeyNode => false,
+            })
+            .cloned()
+            .collect();
+        for slot in released {
+            if let Some(c) = self.children_wrappers.remove(&slot) {
+                drop(unsafe { Box::from_raw(c) });
+            }
+        }
+    }
+    /// Keeps array children on their element after an insert or removal at `at`.
+    /// The child of a removed element is freed.
+    pub(crate) fn reindex_children(&mut self, at: u32, inserted: bool) {
+        if !inserted {
+            self.release_children(|index, key| key.is_none() && index == at);
+        }
+        self.move_children(|index| match index {
+            i if i < at => i,
+            i if inserted => i + 1,
+            i => i - 1,
+        });
+    }
+    /// Keeps array children on their element after the array was reordered.
+    /// `order` holds the old index of each element in its new place.
+    pub(crate) fn permute_children(&mut self, order: &[u32]) {
+        let mut moved_to = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            moved_to[*old as usize] = new as u32;
+        }
+        self.move_children(|index| moved_to.get(index as usize).copied().unwrap_or(index));
+    }
+    /// Points every array child at the index `to` gives for its current one.
+    /// `to` can't send two children to the same index.
+    fn move_children(&mut self, to: impl Fn(u32) -> u32) {
+        self.children_wrappers = std::mem::take(&mut self.children_wrappers)
+            .into_iter()
+            .map(|(slot, c)| {
+                let Slot::Index(old) = slot else {
+                    return (slot, c);
+                };
+                let new = to(old);
+                if let NodeType::Child { index, .. } = unsafe { &mut (*c).node } {
+                    *index = new;
+                }
+                (Slot::Index(new), c)
+            })
+            .collect();
+    }
+    /// Renames the dictionary item behind a child wrapper, keeping its place in the dictionary.
+    /// Fails if another item already has the key.
+    pub(crate) fn rename_item(&mut self, to: &str) -> Result<(), String> {
+        let NodeType::Child {
+            parent,
+            key: Some(key),
+            ..
+        } = &mut self.node
+        else {
+            return Ok(());
+        };
+        let parent = unsafe { &mut **parent };
+        let Some(Value::Dictionary(d)) = parent.borrow_self() else {
+            return Ok(());
+        };
+        // An item that's gone has nothing left to rename
+        if key == to || !d.contains_key(key) {
+            return Ok(());
+        }
+        if d.contains_key(to) {
+            return Err(format!("{to:?} is already a key in the dictionary"));
+        }
+        *d = std::mem::take(d)
+            .into_iter()
+            .map(|(k, v)| {
+                if k == *key {
+                    (to.to_string(), v)
+                } else {
+                    (k, v)
+                }
+            })
+            .collect();
+        // The parent finds this wrapper under its key, anything left under the new one is stale
+        let this = parent.children_wrappers.remove(&Slot::Key(key.clone()));
+        if let Some(this) = this
+            && let Some(stale) = parent
+                .children_wrappers
+                .insert(Slot::Key(to.to_string()), this)
+        {
+            drop(unsafe { Box::from_raw(stale) });
+        }
+        *key = to.to_string();
+        Ok(())
+    }
+    /// Gets the string of a key node, None for everything else
+    pub fn borrow_key(&mut self) -> Option<&mut String> {
         match &mut self.node {
-            NodeType::Node(value) => value,
-            NodeType::Child { node, .. } => unsafe { &mut **node },
-            NodeType::Iterator(_) => panic!("you passed an iterator as a node"),
+            NodeType::Key(key) => Some(key),
+            _ => None,
         }
     }
     pub(crate) fn consume(mut self) -> Option<Value> {
@@ -104,37 +294,202 @@ impl PlistWrapper {
         match node {
             NodeType::Node(v) => Some(v),
             NodeType::Child { .. } => None,
-            NodeType::Iterator(_) => panic!("you passed an iterator as a node"),
+            NodeType::Iterator(_) => None,
+            NodeType::Key(_) => None,
+        }
+    }
+    /// Takes ownership of the value behind a handle, freeing the wrapper.
+    /// Children and iterators can't be moved, so they're left untouched and None is returned.
+    /// # Safety
+    /// The handle must be null or allocated by this library
+    pub(crate) unsafe fn take_handle(handle: plist_t) -> Option<Value> {
+        let wrapper = unsafe { Self::from_handle(handle) }?;
+        if !matches!(wrapper.node, NodeType::Node(_)) {
+            return None;
+        }
+        unsafe { Box::from_raw(handle) }.consume()
+    }
+    /// Frees an iterator handle.
+    /// Anything that isn't an iterator is left alone and reported through the last error.
+    /// # Safety
+    /// The handle must be null or allocated by this library
+    pub(crate) unsafe fn free_iterator(handle: plist_t) {
+        let Some(wrapper) = (unsafe { Self::from_handle(handle) }) else {
+            return;
+        };
+        if !matches!(wrapper.node, NodeType::Iterator(_)) {
+            error::set_last_error("refusing to free a node as an iterator, use plist_free");
+            return;
         }
+        drop(unsafe { Box::from_raw(handle) });
     }
-    pub(crate) fn iter_next(&mut self) -> u32 {
+    pub(crate) fn iter_next(&mut self) -> Option<u32> {
         match &mut self.node {
             NodeType::Iterator(i) => {
                 let to_return = *i;
                 *i += 1;
-                to_return
+                Some(to_return)
             }
-            _ => panic!("you passed a node as an interator"),
+            _ => None,
         }
     }
     pub fn new_node(v: Value) -> Self {
         Self {
             node: NodeType::Node(v),
-            children_wrappers: Vec::new(),
+            children_wrappers: HashMap::new(),
         }
     }
     pub(crate) fn new_iterator(i: u32) -> Self {
         Self {
             node: NodeType::Iterator(i),
-            children_wrappers: Vec::new(),
+            children_wrappers: HashMap::new(),
+        }
+    }
+    pub(crate) fn new_child(parent: *mut PlistWrapper, index: u32, key: Option<String>) -> Self {
+        Self {
+            node: NodeType::Child { parent, index, key },
+            children_wrappers: HashMap::new(),
+        }
+    }
+    pub(crate) fn new_key(key: String) -> Self {
+        Self {
+            node: NodeType::Key(key),
+            children_wrappers: HashMap::new(),
         }
     }
     pub(crate) fn into_ptr(self) -> plist_t {
         let p = Box::new(self);
-        Box::into_raw(p)
+        let p = Box::into_raw(p);
+        handles::register(p, std::ptr::null_mut());
+        p
+    }
+    /// Hands out a child wrapper that this wrapper owns and frees along with itself
+    fn adopt(&mut self, slot: Slot, child: PlistWrapper) -> plist_t {
+        let p = Box::into_raw(Box::new(child));
+        handles::register(p, self);
+        self.children_wrappers.insert(slot, p);
+        p
+    }
+    /// Hands out the child wrapper for an element, reusing the one handed out before
+    /// so repeated lookups don't pile up wrappers, like libplist returning the same node.
+    pub(crate) fn child(&mut self, index: u32, key: Option<String>) -> plist_t {
+        let slot = match key {
+            Some(key) => Slot::Key(key),
+            None => Slot::Index(index),
+        };
+        if let Some(c) = self.children_wrappers.get(&slot) {
+            return *c;
+        }
+        let key = match &slot {
+            Slot::Key(key) => Some(key.clone()),
+            _ => None,
+        };
+        let child = Self::new_child(self, index, key);
+        self.adopt(slot, child)
+    }
+    /// Hands out the key node of a dictionary item, reusing the one handed out before
+    pub(crate) fn key_node(&mut self, key: String) -> plist_t {
+        if let Some(c) = self.children_wrappers.get(&Slot::KeyNode) {
+            return *c;
+        }
+        self.adopt(Slot::KeyNode, Self::new_key(key))
+    }
+    /// Where this wrapper sits in its owner, None for wrappers nothing owns
+    fn slot(&self) -> Option<Slot> {
+        match &self.node {
+            NodeType::Child { key: Some(key), .. } => Some(Slot::Key(key.clone())),
+            NodeType::Child { index, .. } => Some(Slot::Index(*index)),
+            NodeType::Key(_) => Some(Slot::KeyNode),
+            _ => None,
+        }
+    }
+    /// Frees a handle, letting go of it in its owner's `children_wrappers` first
+    /// so the owner doesn't free it again.
+    /// # Safety
+    /// The handle must be null or allocated by this library
+    pub(crate) unsafe fn free_handle(handle: plist_t) {
+        if unsafe { Self::from_handle(handle) }.is_none() {
+            return;
+        }
+        if let Some(owner) = unsafe { handles::owner(handle).as_mut() }
+            && let Some(slot) = unsafe { (*handle).slot() }
+            && owner.children_wrappers.get(&slot) == Some(&handle)
+        {
+            owner.children_wrappers.remove(&slot);
+        }
+        drop(unsafe { Box::from_raw(handle) });
     }
 }
 
+/// Looks up an element in the parent wrapper's container.
+/// Returns None once the element is gone or the parent isn't a container anymore.
+/// # Safety
+/// The parent must be live, which it is for as long as it owns the child
+unsafe fn resolve_child<'a>(
+    parent: *mut PlistWrapper,
+    index: u32,
+    key: &Option<String>,
+) -> Option<&'a mut Value> {
+    match (unsafe { &mut *parent }.borrow_self()?, key) {
+        (Value::Dictionary(d), Some(key)) => d.get_mut(key.as_str()),
+        (Value::Array(a), None) => a.get_mut(index as usize),
+        _ => None,
+    }
+}
+
+/// Borrows the value behind a handle.
+/// Returns None if the handle is null or an iterator.
+/// # Safety
+/// The handle must be null or allocated by this library
+pub(crate) unsafe fn borrow_value<'a>(handle: plist_t) -> Option<&'a mut Value> {
+    unsafe { PlistWrapper::from_handle(handle) }?.borrow_self()
+}
+
+/// Borrows the string behind a key node.
+/// Returns None if the handle is null or not a key.
+/// # Safety
+/// The handle must be null or allocated by this library
+pub(crate) unsafe fn borrow_key<'a>(handle: plist_t) -> Option<&'a mut String> {
+    unsafe { PlistWrapper::from_handle(handle) }?.borrow_key()
+}
+
+/// Borrows a C string.
+/// Returns None if the pointer is null or the string isn't valid UTF-8.
+/// # Safety
+/// The pointer must be null or point to a null terminated string
+pub(crate) unsafe fn borrow_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// Borrows a buffer passed in from C.
+/// Returns None if the pointer is null, unless the buffer is empty.
+/// # Safety
+/// The pointer must be valid for `length` bytes
+pub(crate) unsafe fn borrow_bytes<'a>(data: *const u8, length: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return if length == 0 { Some(&[]) } else { None };
+    }
+    Some(unsafe { std::slice::from_raw_parts(data, length) })
+}
+
+/// Runs the body of an exported function, returning `default` if it panics.
+/// Unwinding out of an `extern "C"` function aborts the host process.
+/// The panic message is kept for `plist_get_last_error`.
+pub(crate) fn ffi_guard<T>(default: T, body: impl FnOnce() -> T) -> T {
+    std::panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        error::set_last_error(format_args!("internal error: {message}"));
+        default
+    })
+}
+
 impl From<Value> for PlistWrapper {
     fn from(value: Value) -> Self {
         Self::new_node(value)
@@ -145,18 +500,24 @@ impl Clone for PlistWrapper {
     fn clone(&self) -> Self {
         match &self.node {
             NodeType::Node(value) => PlistWrapper::new_node(value.clone()),
-            NodeType::Child { node, .. } => unsafe {
-                let cloned = (**node).clone();
+            NodeType::Child { parent, index, key } => {
+                // A child whose element is gone has nothing to copy
+                let cloned = unsafe { resolve_child(*parent, *index, key) }
+                    .map(|v| v.clone())
+                    .unwrap_or_else(null::null);
                 PlistWrapper::new_node(cloned)
-            },
+            }
             NodeType::Iterator(i) => PlistWrapper::new_iterator(*i),
+            NodeType::Key(k) => PlistWrapper::new_key(k.clone()),
         }
     }
 }
 
 impl Drop for PlistWrapper {
     fn drop(&mut self) {
-        for c in &self.children_wrappers {
+        handles::unregister(self);
+        // Children the caller already freed aren't live anymore and get skipped
+        for c in self.children_wrappers.values() {
             unsafe {
                 creation::plist_free(*c);
             }
@@ -0,0 +1,233 @@
+// Jackson Coxson
+// plist has no null type, so null nodes are carried around as a date no document can hold.
+// The JSON and binary formats translate it to and from their real null.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use plist::{Date, Uid, Value};
+
+/// Seconds past the unix epoch of the date standing in for null, in the year 11476.
+/// XML dates stop at 9999, and a binary date this far out is too coarse for
+/// the extra nanosecond. The date setters work in whole microseconds at most.
+const NULL_DATE_SECS: u64 = 300_000_000_000;
+
+/// The binary plist null marker
+const NULL_OBJECT: [u8; 1] = [0x00];
+
+/// Size of the binary plist trailer
+const TRAILER_SIZE: usize = 32;
+
+fn null_date() -> Date {
+    (UNIX_EPOCH + Duration::new(NULL_DATE_SECS, 1)).into()
+}
+
+/// Creates a null node
+pub(crate) fn null() -> Value {
+    Value::Date(null_date())
+}
+
+/// Whether the value is a null node
+pub(crate) fn is_null(value: &Value) -> bool {
+    matches!(value, Value::Date(d) if *d == null_date())
+}
+
+/// Whether there's a null node anywhere in the tree
+pub(crate) fn contains_null(value: &Value) -> bool {
+    match value {
+        Value::Array(a) => a.iter().any(contains_null),
+        Value::Dictionary(d) => d.values().any(contains_null),
+        v => is_null(v),
+    }
+}
+
+/// Writes a binary plist with real null markers.
+/// The plist crate writes a UID the document doesn't otherwise use in their place,
+/// which then gets swapped for the marker.
+pub(crate) fn to_binary(value: &Value) -> Result<Vec<u8>, String> {
+    let mut uids = Vec::new();
+    collect_uids(value, &mut uids);
+    let stand_in = unused_uid(&uids);
+
+    let mut value = value.clone();
+    replace_nulls(&mut value, stand_in);
+    let mut buf = Vec::new();
+    plist::to_writer_binary(&mut buf, &value).map_err(|e| e.to_string())?;
+
+    let layout = Layout::read(&buf).ok_or("the plist crate wrote a malformed binary plist")?;
+    Ok(layout.replace(&buf, |o| o == uid_object(stand_in), &NULL_OBJECT))
+}
+
+/// Reads a binary plist, turning its null markers into null nodes.
+/// They're swapped for a UID the document doesn't otherwise use before the plist crate sees them,
+/// and offset tables of odd widths are rewritten to one it can read.
+pub(crate) fn from_binary(data: &[u8]) -> Result<Value, plist::Error> {
+    let read = |data: &[u8]| Value::from_reader(std::io::Cursor::new(data));
+    // Malformed documents are left for the plist crate to report
+    let Some(layout) = Layout::read(data) else {
+        return read(data);
+    };
+    if !layout.objects(data).any(|o| o == NULL_OBJECT) {
+        // The plist crate only reads offset tables of 1, 2, 4 or 8 bytes
+        if matches!(layout.offset_size, 1 | 2 | 4 | 8) {
+            return read(data);
+        }
+        return read(&layout.replace(data, |_| false, &[]));
+    }
+
+    let uids: Vec<u64> = layout.objects(data).filter_map(read_uid).collect();
+    let stand_in = unused_uid(&uids);
+    let patched = layout.replace(data, |o| o == NULL_OBJECT, &uid_object(stand_in));
+    let mut value = read(&patched)?;
+    restore_nulls(&mut value, stand_in);
+    Ok(value)
+}
+
+fn collect_uids(value: &Value, uids: &mut Vec<u64>) {
+    match value {
+        Value::Array(a) => a.iter().for_each(|v| collect_uids(v, uids)),
+        Value::Dictionary(d) => d.values().for_each(|v| collect_uids(v, uids)),
+        Value::Uid(u) => uids.push(u.get()),
+        _ => {}
+    }
+}
+
+/// The largest UID not in `uids`, which the plist crate always writes with 8 bytes
+fn unused_uid(uids: &[u64]) -> u64 {
+    let mut uids = uids.to_vec();
+    uids.sort_unstable_by(|l, r| r.cmp(l));
+    uids.dedup();
+    // The first gap counting down from the top, there are fewer UIDs than u64s
+    let mut candidate = u64::MAX;
+    for uid in uids {
+        if uid != candidate {
+            break;
+        }
+        candidate -= 1;
+    }
+    candidate
+}
+
+fn uid_object(uid: u64) -> [u8; 9] {
+    let mut object = [0x87; 9];
+    object[1..].copy_from_slice(&uid.to_be_bytes());
+    object
+}
+
+/// The value of a binary UID object, 0x8n followed by n + 1 bytes
+fn read_uid(object: &[u8]) -> Option<u64> {
+    let (marker, rest) = object.split_first()?;
+    if marker >> 4 != 0x8 || marker & 0xf >= 8 {
+        return None;
+    }
+    rest.get(..(marker & 0xf) as usize + 1).map(read_be)
+}
+
+fn replace_nulls(value: &mut Value, stand_in: u64) {
+    match value {
+        Value::Array(a) => a.iter_mut().for_each(|v| replace_nulls(v, stand_in)),
+        Value::Dictionary(d) => d.values_mut().for_each(|v| replace_nulls(v, stand_in)),
+        v if is_null(v) => *v = Value::Uid(Uid::new(stand_in)),
+        _ => {}
+    }
+}
+
+fn restore_nulls(value: &mut Value, stand_in: u64) {
+    match value {
+        Value::Array(a) => a.iter_mut().for_each(|v| restore_nulls(v, stand_in)),
+        Value::Dictionary(d) => d.values_mut().for_each(|v| restore_nulls(v, stand_in)),
+        Value::Uid(u) if u.get() == stand_in => *value = null(),
+        _ => {}
+    }
+}
+
+/// Where the objects of a binary plist are, going by its offset table
+struct Layout<'a> {
+    trailer: &'a [u8],
+    offset_size: usize,
+    /// The offset table, in object order
+    offsets: Vec<usize>,
+    /// The same offsets sorted, objects run from one to the next
+    starts: Vec<usize>,
+    table_offset: usize,
+}
+
+impl<'a> Layout<'a> {
+    fn read(data: &'a [u8]) -> Option<Self> {
+        let trailer = data.len().checked_sub(TRAILER_SIZE).map(|t| &data[t..])?;
+        let offset_size = trailer[6] as usize;
+        let num_objects = usize::try_from(read_be(&trailer[8..16])).ok()?;
+        let table_offset = usize::try_from(read_be(&trailer[24..32])).ok()?;
+        if !(1..=8).contains(&offset_size) {
+            return None;
+        }
+        let table = data
+            .get(table_offset..table_offset.checked_add(num_objects.checked_mul(offset_size)?)?)?;
+
+        let offsets: Vec<usize> = table
+            .chunks_exact(offset_size)
+            .map(|o| {
+                usize::try_from(read_be(o))
+                    .ok()
+                    .filter(|o| *o < table_offset)
+            })
+            .collect::<Option<_>>()?;
+        let mut starts = offsets.clone();
+        starts.sort_unstable();
+        starts.dedup();
+        starts.first()?;
+
+        Some(Self {
+            trailer,
+            offset_size,
+            offsets,
+            starts,
+            table_offset,
+        })
+    }
+
+    fn objects<'d>(&'d self, data: &'d [u8]) -> impl Iterator<Item = &'d [u8]> + 'd {
+        self.starts.iter().enumerate().map(move |(i, s)| {
+            &data[*s..self.starts.get(i + 1).copied().unwrap_or(self.table_offset)]
+        })
+    }
+
+    /// Rewrites every object that matches as `to`.
+    /// The objects are copied in order, so the ones after a rewritten object
+    /// move and the offset table is rebuilt to point at them.
+    fn replace(&self, data: &[u8], is_match: impl Fn(&[u8]) -> bool, to: &[u8]) -> Vec<u8> {
+        let mut out = data[..self.starts[0]].to_vec();
+        let mut moved = Vec::with_capacity(self.starts.len());
+        for o in self.objects(data) {
+            moved.push(out.len());
+            if is_match(o) {
+                out.extend_from_slice(to);
+            } else {
+                out.extend_from_slice(o);
+            }
+        }
+
+        let new_table_offset = out.len();
+        let new_offset_size = match out.len() {
+            l if l <= u8::MAX as usize => 1,
+            l if l <= u16::MAX as usize => 2,
+            l if l <= u32::MAX as usize => 4,
+            _ => 8,
+        };
+        for o in &self.offsets {
+            // starts is sorted and holds every offset, so this can't miss
+            let i = self.starts.binary_search(o).unwrap_or_default();
+            out.extend_from_slice(&(moved[i] as u64).to_be_bytes()[8 - new_offset_size..]);
+        }
+
+        out.extend_from_slice(&self.trailer[..6]);
+        out.push(new_offset_size as u8);
+        out.extend_from_slice(&self.trailer[7..24]);
+        out.extend_from_slice(&(new_table_offset as u64).to_be_bytes());
+        out
+    }
+}
+
+/// Reads a big endian unsigned integer of up to 8 bytes
+fn read_be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
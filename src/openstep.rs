@@ -0,0 +1,522 @@
+// Jackson Coxson
+// OpenStep (NeXTSTEP) plist support, modeled after libplist's oplist.c
+
+use plist::Value;
+
+use crate::{PlistErr, error::fail};
+
+/// Characters that can be written without quoting a string,
+/// the same set libplist and CoreFoundation write unquoted
+const UNQUOTED_CHARSET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_$/:.-";
+
+/// Characters an unquoted string is read from, `+` is accepted but never written unquoted
+const UNQUOTED_READ_CHARSET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_$+/:.-";
+
+/// Serializes a value into the OpenStep format.
+/// Booleans, dates and UIDs can't be represented and return PLIST_ERR_FORMAT.
+///
+/// # Arguments
+/// * `value` - The value to serialize
+/// * `prettify` - Whether to indent the output like libplist does
+pub fn to_openstep(value: &Value, prettify: bool) -> Result<Vec<u8>, PlistErr> {
+    let mut out = Vec::new();
+    write_node(value, &mut out, 0, prettify)?;
+    if prettify {
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn write_indent(out: &mut Vec<u8>, depth: usize) {
+    for _ in 0..depth {
+        out.extend_from_slice(b"  ");
+    }
+}
+
+fn write_node(
+    value: &Value,
+    out: &mut Vec<u8>,
+    depth: usize,
+    prettify: bool,
+) -> Result<(), PlistErr> {
+    match value {
+        Value::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        Value::Real(r) => out.extend_from_slice(format_real(*r).as_bytes()),
+        Value::String(s) => write_string(s, out),
+        Value::Array(a) => {
+            out.push(b'(');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                if prettify {
+                    out.push(b'\n');
+                    write_indent(out, depth + 1);
+                }
+                write_node(v, out, depth + 1, prettify)?;
+            }
+            if !a.is_empty() && prettify {
+                out.push(b'\n');
+                write_indent(out, depth);
+            }
+            out.push(b')');
+        }
+        Value::Dictionary(d) => {
+            out.push(b'{');
+            for (k, v) in d.iter() {
+                if prettify {
+                    out.push(b'\n');
+                    write_indent(out, depth + 1);
+                }
+                write_string(k, out);
+                if prettify {
+                    out.extend_from_slice(b" = ");
+                } else {
+                    out.push(b'=');
+                }
+                write_node(v, out, depth + 1, prettify)?;
+                out.push(b';');
+            }
+            if !d.is_empty() && prettify {
+                out.push(b'\n');
+                write_indent(out, depth);
+            }
+            out.push(b'}');
+        }
+        Value::Data(d) => {
+            out.push(b'<');
+            for (i, b) in d.iter().enumerate() {
+                out.extend_from_slice(format!("{b:02x}").as_bytes());
+                if prettify && i % 4 == 3 && i < d.len() - 1 {
+                    out.push(b' ');
+                }
+            }
+            out.push(b'>');
+        }
+        // Booleans, dates, UIDs and null have no OpenStep representation
+        _ => {
+            return Err(fail(
+                PlistErr::PLIST_ERR_FORMAT,
+                "OpenStep can't represent booleans, dates, UIDs or null",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a string, quoting and escaping it if it contains anything
+/// outside of the unquoted charset.
+fn write_string(s: &str, out: &mut Vec<u8>) {
+    if !s.is_empty() && s.bytes().all(|b| UNQUOTED_CHARSET.contains(&b)) {
+        out.extend_from_slice(s.as_bytes());
+        return;
+    }
+    out.push(b'"');
+    for b in s.bytes() {
+        match b {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            0x07 => out.extend_from_slice(b"\\a"),
+            0x08 => out.extend_from_slice(b"\\b"),
+            0x0b => out.extend_from_slice(b"\\v"),
+            0x0c => out.extend_from_slice(b"\\f"),
+            b if b < 0x20 => out.extend_from_slice(format!("\\{b:03o}").as_bytes()),
+            b => out.push(b),
+        }
+    }
+    out.push(b'"');
+}
+
+/// Formats a real the way libplist's dtostr does
+pub(crate) fn format_real(r: f64) -> String {
+    if r.is_nan() {
+        "nan".to_string()
+    } else if r.is_infinite() {
+        if r > 0.0 { "+infinity" } else { "-infinity" }.to_string()
+    } else if r == 0.0 {
+        "0.0".to_string()
+    } else {
+        format_g(r, 17)
+    }
+}
+
+/// Emulates C's `%.*g` formatting
+pub(crate) fn format_g(r: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    // Let the scientific formatter do the rounding, then decide on the style
+    let sci = format!("{:.*e}", precision - 1, r);
+    let (mantissa, exponent) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+
+    if exponent < -4 || exponent >= precision as i32 {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exponent.abs())
+    } else {
+        let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+        trim_fraction(&format!("{r:.decimals$}")).to_string()
+    }
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+/// An error encountered while parsing an OpenStep plist
+#[derive(Debug)]
+pub struct OpenStepError {
+    pub message: &'static str,
+    pub offset: usize,
+}
+
+impl std::fmt::Display for OpenStepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for OpenStepError {}
+
+/// Parses an OpenStep plist, including `.strings` files that omit the
+/// braces around the root dictionary.
+///
+/// # Arguments
+/// * `data` - The raw bytes of the document
+pub fn from_openstep(data: &[u8]) -> Result<Value, OpenStepError> {
+    let mut parser = Parser { data, pos: 0 };
+    parser.skip_whitespace()?;
+    if parser.peek().is_none() {
+        // An empty strings file is an empty dictionary
+        return Ok(Value::Dictionary(plist::Dictionary::new()));
+    }
+
+    let root = parser.parse_node(0)?;
+    parser.skip_whitespace()?;
+
+    let root = match (root, parser.peek()) {
+        // key = value; pairs without the surrounding braces
+        (Value::String(first_key), Some(b'=')) => {
+            let mut d = plist::Dictionary::new();
+            parser.pos += 1;
+            let v = parser.parse_node(1)?;
+            parser.expect(b';')?;
+            d.insert(first_key, v);
+            parser.parse_dict_entries(&mut d, None, 1)?;
+            Value::Dictionary(d)
+        }
+        (root, _) => root,
+    };
+
+    parser.skip_whitespace()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected data after the root node"));
+    }
+    Ok(root)
+}
+
+/// Deeply nested input shouldn't be able to blow the stack
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> OpenStepError {
+        OpenStepError {
+            message,
+            offset: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    /// Skips whitespace and both styles of comments
+    fn skip_whitespace(&mut self) -> Result<(), OpenStepError> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n' | 0x0b | 0x0c) => self.pos += 1,
+                Some(b'/') => match self.data.get(self.pos + 1) {
+                    Some(b'/') => {
+                        while let Some(c) = self.next() {
+                            if c == b'\n' {
+                                break;
+                            }
+                        }
+                    }
+                    Some(b'*') => {
+                        let start = self.pos;
+                        self.pos += 2;
+                        loop {
+                            match self.next() {
+                                Some(b'*') if self.peek() == Some(b'/') => {
+                                    self.pos += 1;
+                                    break;
+                                }
+                                Some(_) => {}
+                                None => {
+                                    self.pos = start;
+                                    return Err(self.error("unterminated comment"));
+                                }
+                            }
+                        }
+                    }
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), OpenStepError> {
+        self.skip_whitespace()?;
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(match c {
+                b';' => self.error("missing ';'"),
+                b'=' => self.error("missing '='"),
+                _ => self.error("unexpected character"),
+            })
+        }
+    }
+
+    fn parse_node(&mut self, depth: usize) -> Result<Value, OpenStepError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too many levels of nesting"));
+        }
+        self.skip_whitespace()?;
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut d = plist::Dictionary::new();
+                self.parse_dict_entries(&mut d, Some(b'}'), depth + 1)?;
+                Ok(Value::Dictionary(d))
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                self.parse_array(depth + 1)
+            }
+            Some(b'<') => {
+                self.pos += 1;
+                self.parse_data()
+            }
+            Some(q @ (b'"' | b'\'')) => {
+                self.pos += 1;
+                Ok(Value::String(self.parse_quoted_string(q)?))
+            }
+            Some(c) if UNQUOTED_READ_CHARSET.contains(&c) => {
+                Ok(Value::String(self.parse_unquoted_string()))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of data")),
+        }
+    }
+
+    /// Parses `key = value;` pairs until `end` is found, or until the end of
+    /// the data when `end` is None.
+    fn parse_dict_entries(
+        &mut self,
+        d: &mut plist::Dictionary,
+        end: Option<u8>,
+        depth: usize,
+    ) -> Result<(), OpenStepError> {
+        loop {
+            self.skip_whitespace()?;
+            match (self.peek(), end) {
+                (Some(c), Some(e)) if c == e => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                (None, None) => return Ok(()),
+                (None, Some(_)) => return Err(self.error("unterminated dictionary")),
+                _ => {}
+            }
+
+            let key = match self.parse_node(depth)? {
+                Value::String(k) => k,
+                _ => return Err(self.error("dictionary keys must be strings")),
+            };
+            self.expect(b'=')?;
+            let value = self.parse_node(depth)?;
+            self.expect(b';')?;
+            d.insert(key, value);
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Value, OpenStepError> {
+        let mut a = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            match self.peek() {
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(a));
+                }
+                None => return Err(self.error("unterminated array")),
+                _ => {}
+            }
+            a.push(self.parse_node(depth)?);
+            self.skip_whitespace()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b')') => {}
+                None => return Err(self.error("unterminated array")),
+                _ => return Err(self.error("missing ',' between array items")),
+            }
+        }
+    }
+
+    fn parse_data(&mut self) -> Result<Value, OpenStepError> {
+        let mut bytes = Vec::new();
+        let mut high: Option<u8> = None;
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated data")),
+            };
+            let nibble = match c {
+                b'>' => {
+                    if high.is_some() {
+                        return Err(self.error("odd number of hex digits in data"));
+                    }
+                    return Ok(Value::Data(bytes));
+                }
+                b' ' | b'\t' | b'\r' | b'\n' => continue,
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                b'A'..=b'F' => c - b'A' + 10,
+                _ => return Err(self.error("invalid character in data")),
+            };
+            match high.take() {
+                Some(h) => bytes.push((h << 4) | nibble),
+                None => high = Some(nibble),
+            }
+        }
+    }
+
+    fn parse_unquoted_string(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !UNQUOTED_READ_CHARSET.contains(&c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        // The charset is pure ASCII
+        String::from_utf8_lossy(&self.data[start..self.pos]).into_owned()
+    }
+
+    fn parse_quoted_string(&mut self, quote: u8) -> Result<String, OpenStepError> {
+        let start = self.pos;
+        let mut buf = Vec::new();
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("unterminated string"));
+                }
+            };
+            if c == quote {
+                break;
+            }
+            if c != b'\\' {
+                buf.push(c);
+                continue;
+            }
+            let escaped = match self.next() {
+                Some(e) => e,
+                None => return Err(self.error("unterminated string")),
+            };
+            match escaped {
+                b'a' => buf.push(0x07),
+                b'b' => buf.push(0x08),
+                b'f' => buf.push(0x0c),
+                b'n' => buf.push(b'\n'),
+                b'r' => buf.push(b'\r'),
+                b't' => buf.push(b'\t'),
+                b'v' => buf.push(0x0b),
+                b'U' | b'u' => {
+                    let c = self.parse_unicode_escape()?;
+                    let mut tmp = [0; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                }
+                b'0'..=b'7' => {
+                    let mut v = (escaped - b'0') as u32;
+                    for _ in 0..2 {
+                        match self.peek() {
+                            Some(d @ b'0'..=b'7') => {
+                                v = v * 8 + (d - b'0') as u32;
+                                self.pos += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    if v > 0xff {
+                        return Err(self.error("invalid octal escape"));
+                    }
+                    let mut tmp = [0; 4];
+                    let c = char::from_u32(v).unwrap_or(char::REPLACEMENT_CHARACTER);
+                    buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                }
+                other => buf.push(other),
+            }
+        }
+        String::from_utf8(buf).map_err(|_| OpenStepError {
+            message: "invalid UTF-8 in string",
+            offset: start,
+        })
+    }
+
+    /// Parses the 4 hex digits after `\U`, pairing up UTF-16 surrogates
+    fn parse_unicode_escape(&mut self) -> Result<char, OpenStepError> {
+        let first = self.parse_hex4()?;
+        if (0xd800..0xdc00).contains(&first)
+            && self.data.get(self.pos) == Some(&b'\\')
+            && matches!(self.data.get(self.pos + 1), Some(b'U' | b'u'))
+        {
+            self.pos += 2;
+            let second = self.parse_hex4()?;
+            return char::decode_utf16([first, second])
+                .next()
+                .and_then(|c| c.ok())
+                .ok_or_else(|| self.error("invalid unicode escape"));
+        }
+        char::from_u32(first as u32).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u16, OpenStepError> {
+        let digits = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let digits =
+            std::str::from_utf8(digits).map_err(|_| self.error("invalid unicode escape"))?;
+        let v =
+            u16::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(v)
+    }
+}
@@ -0,0 +1,161 @@
+// Jackson Coxson
+// Applies lists of add, remove, replace, move, copy and test operations, RFC 6902 style
+
+use plist::Value;
+
+use crate::{
+    PlistErr, PlistWrapper, borrow_value, error::fail, ffi_guard, path, plist_err_t, plist_t,
+};
+
+/// Paths starting with `:` are PlistBuddy style, anything else is a JSON Pointer
+fn parse(path: &str) -> Result<Vec<String>, String> {
+    if path.starts_with(':') {
+        return Ok(path::parse_path(path));
+    }
+    path::parse_pointer(path).ok_or_else(|| format!("{path:?} isn't a valid path"))
+}
+
+fn index(token: &str, len: usize, appending: bool) -> Result<usize, String> {
+    if appending && token == "-" {
+        return Ok(len);
+    }
+    match token.parse::<usize>() {
+        Ok(i) if i < len || (appending && i == len) => Ok(i),
+        Ok(i) => Err(format!("index {i} is out of bounds")),
+        Err(_) => Err(format!("{token:?} isn't an array index")),
+    }
+}
+
+fn get<'a>(root: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    path.iter().try_fold(root, |current, token| match current {
+        Value::Dictionary(d) => d
+            .get_mut(token)
+            .ok_or_else(|| format!("{token:?} doesn't exist")),
+        Value::Array(a) => {
+            let i = index(token, a.len(), false)?;
+            Ok(&mut a[i])
+        }
+        _ => Err(format!("can't look up {token:?} in a non-container")),
+    })
+}
+
+/// Adds a value, replacing a dictionary entry or inserting into an array
+fn add(root: &mut Value, path: &[String], value: Value) -> Result<(), String> {
+    let Some((token, parent)) = path.split_last() else {
+        *root = value;
+        return Ok(());
+    };
+    match get(root, parent)? {
+        Value::Dictionary(d) => {
+            d.insert(token.clone(), value);
+        }
+        Value::Array(a) => {
+            let i = index(token, a.len(), true)?;
+            a.insert(i, value);
+        }
+        _ => return Err(format!("can't add {token:?} to a non-container")),
+    }
+    Ok(())
+}
+
+fn remove(root: &mut Value, path: &[String]) -> Result<Value, String> {
+    let Some((token, parent)) = path.split_last() else {
+        return Err("can't remove the root".to_string());
+    };
+    match get(root, parent)? {
+        Value::Dictionary(d) => d
+            .remove(token)
+            .ok_or_else(|| format!("{token:?} doesn't exist")),
+        Value::Array(a) => {
+            let i = index(token, a.len(), false)?;
+            Ok(a.remove(i))
+        }
+        _ => Err(format!("can't remove {token:?} from a non-container")),
+    }
+}
+
+/// Applies one operation.
+/// Diff records from plist_diff work too, `change` is a replace and the value is under `new`.
+fn apply(root: &mut Value, op: &Value) -> Result<(), String> {
+    let Value::Dictionary(op) = op else {
+        return Err("isn't a dictionary".to_string());
+    };
+    let string = |key: &str| match op.get(key) {
+        Some(Value::String(s)) => Ok(s.as_str()),
+        _ => Err(format!("needs a string {key:?}")),
+    };
+    let value = || {
+        op.get("value")
+            .or_else(|| op.get("new"))
+            .cloned()
+            .ok_or_else(|| "needs a \"value\"".to_string())
+    };
+    let path = parse(string("path")?)?;
+
+    match string("op")? {
+        "add" => add(root, &path, value()?),
+        "remove" => remove(root, &path).map(|_| ()),
+        "replace" | "change" => {
+            *get(root, &path)? = value()?;
+            Ok(())
+        }
+        "move" => {
+            let from = parse(string("from")?)?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                return Err("can't move a value into itself".to_string());
+            }
+            let moved = remove(root, &from)?;
+            add(root, &path, moved)
+        }
+        "copy" => {
+            let copied = get(root, &parse(string("from")?)?)?.clone();
+            add(root, &path, copied)
+        }
+        "test" => {
+            if *get(root, &path)? == value()? {
+                Ok(())
+            } else {
+                Err("test failed, the values differ".to_string())
+            }
+        }
+        other => Err(format!("unknown op {other:?}")),
+    }
+}
+
+/// Applies a list of operations, all or nothing.
+/// Each operation is a dictionary with `op` (`add`, `remove`, `replace`, `move`, `copy` or `test`),
+/// a `path`, and `value` or `from` where the op needs them, like RFC 6902 JSON Patch.
+/// A JSON Patch document read with plist_from_json works as is, and so does plist_diff's output.
+/// Paths are JSON Pointers, or PlistBuddy style when they start with `:`.
+/// If an operation fails the plist is left untouched and the error names it.
+/// Otherwise handles to nodes inside the plist turn invalid.
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_patch(plist: plist_t, ops: plist_t) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        let Some(Value::Array(ops)) = (unsafe { borrow_value(ops) }).cloned() else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "ops isn't an array");
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(plist) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        };
+        let Some(target) = wrapper.borrow_self() else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "plist isn't a node");
+        };
+
+        // Worked on a copy, so a failure part way through changes nothing
+        let mut patched = target.clone();
+        for (i, op) in ops.iter().enumerate() {
+            if let Err(e) = apply(&mut patched, op) {
+                return fail(
+                    PlistErr::PLIST_ERR_INVALID_ARG,
+                    format!("operation {i}: {e}"),
+                );
+            }
+        }
+        *target = patched;
+        wrapper.release_children(|_, _| true);
+        PlistErr::PLIST_ERR_SUCCESS
+    })
+}
@@ -0,0 +1,380 @@
+// Jackson Coxson
+// PlistBuddy style string paths, `:Devices:0:UDID` or `/Devices/0/UDID`
+
+use plist::{Dictionary, Value};
+use std::ffi::c_char;
+
+use crate::{
+    NodeType, PlistErr, PlistWrapper, borrow_str, error::fail, ffi_guard, null, plist_err_t,
+    plist_t,
+};
+
+/// How a path spells the empty key, which a bare empty component can't
+const EMPTY_KEY: &str = "\"\"";
+
+/// Splits a path into its keys and indexes.
+/// A leading `:` or `/` picks the separator, `:` is used otherwise.
+/// A backslash escapes the next character, empty components are skipped.
+/// A component of just `""` is the empty key, `\"\"` is two quotes.
+pub(crate) fn parse_path(path: &str) -> Vec<String> {
+    let (separator, path) = match path.chars().next() {
+        Some(c @ (':' | '/')) => (c, &path[1..]),
+        _ => (':', path),
+    };
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    let mut chars = path.chars();
+    let mut finish = |current: &mut String, escaped: &mut bool| {
+        let segment = std::mem::take(current);
+        if !std::mem::take(escaped) && segment == EMPTY_KEY {
+            segments.push(String::new());
+        } else if !segment.is_empty() {
+            segments.push(segment);
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                escaped = true;
+                current.extend(chars.next());
+            }
+            c if c == separator => finish(&mut current, &mut escaped),
+            c => current.push(c),
+        }
+    }
+    finish(&mut current, &mut escaped);
+    segments
+}
+
+/// Splits a JSON Pointer, RFC 6901, into its reference tokens.
+/// Returns None if it's not empty and doesn't start with `/`.
+pub(crate) fn parse_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    Some(
+        pointer
+            .strip_prefix('/')?
+            .split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Joins segments into a `:` separated path that parse_path splits back apart
+pub(crate) fn join_path(segments: &[String]) -> String {
+    if segments.is_empty() {
+        return ":".to_string();
+    }
+    segments.iter().fold(String::new(), |mut path, segment| {
+        path.push(':');
+        match segment.as_str() {
+            "" => path.push_str(EMPTY_KEY),
+            EMPTY_KEY => path.push_str("\\\"\""),
+            segment => {
+                for c in segment.chars() {
+                    if matches!(c, ':' | '\\') {
+                        path.push('\\');
+                    }
+                    path.push(c);
+                }
+            }
+        }
+        path
+    })
+}
+
+/// Looks up one step of a path.
+/// Returns None if the key is missing or the index is one past the end,
+/// where a set would add the element.
+fn lookup(wrapper: &mut PlistWrapper, segment: &str) -> Result<Option<plist_t>, plist_err_t> {
+    match wrapper.borrow_self() {
+        Some(Value::Dictionary(d)) => Ok(d
+            .contains_key(segment)
+            .then(|| wrapper.child(u32::MAX, Some(segment.to_string())))),
+        Some(Value::Array(a)) => match array_index(segment)? as usize {
+            i if i < a.len() => Ok(Some(wrapper.child(i as u32, None))),
+            i if i == a.len() => Ok(None),
+            i => Err(fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                format!("index {i} is past the end of an array of {}", a.len()),
+            )),
+        },
+        _ => Err(fail(
+            PlistErr::PLIST_ERR_INVALID_ARG,
+            format!("can't look up {segment:?}, the node isn't a dictionary or array"),
+        )),
+    }
+}
+
+fn array_index(segment: &str) -> Result<u32, plist_err_t> {
+    segment.parse().map_err(|_| {
+        fail(
+            PlistErr::PLIST_ERR_INVALID_ARG,
+            format!("{segment:?} isn't an array index"),
+        )
+    })
+}
+
+/// Walks every segment, failing on the first one that doesn't exist
+fn resolve(plist: plist_t, segments: &[String]) -> Result<plist_t, plist_err_t> {
+    let mut current = plist;
+    for segment in segments {
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(current) }) else {
+            return Err(fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                "invalid plist handle",
+            ));
+        };
+        current = lookup(wrapper, segment)?.ok_or_else(|| {
+            fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                format!("{segment:?} doesn't exist"),
+            )
+        })?;
+    }
+    Ok(current)
+}
+
+/// Stores a value under the segment, replacing what's there.
+/// The value is only made once the segment is known to fit,
+/// so a failure leaves the caller's item alone.
+fn put(
+    wrapper: &mut PlistWrapper,
+    segment: &str,
+    value: impl FnOnce() -> Value,
+) -> Result<(), plist_err_t> {
+    match wrapper.borrow_self() {
+        Some(Value::Dictionary(d)) => {
+            if d.insert(segment.to_string(), value()).is_some() {
+                wrapper.release_children(|_, k| k == Some(segment));
+            }
+        }
+        Some(Value::Array(a)) => {
+            let i = array_index(segment)?;
+            match (i as usize).cmp(&a.len()) {
+                std::cmp::Ordering::Less => {
+                    a[i as usize] = value();
+                    wrapper.release_children(|index, key| key.is_none() && index == i);
+                }
+                std::cmp::Ordering::Equal => a.push(value()),
+                std::cmp::Ordering::Greater => {
+                    return Err(fail(
+                        PlistErr::PLIST_ERR_INVALID_ARG,
+                        format!("index {i} is past the end of an array of {}", a.len()),
+                    ));
+                }
+            }
+        }
+        _ => {
+            return Err(fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                format!("can't set {segment:?}, the node isn't a dictionary or array"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Wraps the value in the containers the segments call for.
+/// `0` makes an array, anything else a dictionary.
+fn build(segments: &[String], value: Value) -> Value {
+    segments.iter().rev().fold(value, |value, segment| {
+        if segment == "0" {
+            Value::Array(vec![value])
+        } else {
+            let mut d = Dictionary::new();
+            d.insert(segment.clone(), value);
+            Value::Dictionary(d)
+        }
+    })
+}
+
+/// Finds the node at a path like `:Devices:0:UDID` or `/Devices/0/UDID`.
+/// An empty path, `:` or `/`, is the node itself.
+/// The node belongs to its parent, don't free it.
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_path_get_item(
+    plist: plist_t,
+    path: *const c_char,
+    item: *mut plist_t,
+) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        let Some(path) = (unsafe { borrow_str(path) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "path is null or not UTF-8");
+        };
+        if item.is_null() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "item is null");
+        }
+        unsafe { *item = std::ptr::null_mut() };
+        if unsafe { PlistWrapper::from_handle(plist) }.is_none() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        }
+        match resolve(plist, &parse_path(path)) {
+            Ok(node) => {
+                unsafe { *item = node };
+                PlistErr::PLIST_ERR_SUCCESS
+            }
+            Err(e) => e,
+        }
+    })
+}
+
+/// Sets the node at a path, creating the dictionaries and arrays missing along the way.
+/// A missing `0` creates an array, any other missing segment a dictionary.
+/// An index one past the end of an array appends.
+/// On success the plist owns the item, on failure it's left to the caller.
+/// # Safety
+/// Don't pass a bad plist >:(
+/// Don't move a child
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_path_set_item(
+    plist: plist_t,
+    path: *const c_char,
+    item: plist_t,
+) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        let Some(path) = (unsafe { borrow_str(path) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "path is null or not UTF-8");
+        };
+        if unsafe { PlistWrapper::from_handle(plist) }.is_none() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        }
+        if item == plist
+            || !matches!(
+                unsafe { PlistWrapper::from_handle(item) }.map(|w| &w.node),
+                Some(NodeType::Node(_))
+            )
+        {
+            return fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                "the item has to be a node of its own, not a child or the plist itself",
+            );
+        }
+        let segments = parse_path(path);
+        let Some(last) = segments.len().checked_sub(1) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "can't replace the root");
+        };
+        // Nothing fails after put calls this
+        let take = || unsafe { PlistWrapper::take_handle(item) }.unwrap_or_else(null::null);
+
+        let mut current = plist;
+        for (i, segment) in segments.iter().enumerate() {
+            let Some(wrapper) = (unsafe { PlistWrapper::from_handle(current) }) else {
+                return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+            };
+            if i == last {
+                return match put(wrapper, segment, take) {
+                    Ok(()) => PlistErr::PLIST_ERR_SUCCESS,
+                    Err(e) => e,
+                };
+            }
+            match lookup(wrapper, segment) {
+                Ok(Some(next)) => current = next,
+                Ok(None) => {
+                    let rest = &segments[i + 1..];
+                    return match put(wrapper, segment, || build(rest, take())) {
+                        Ok(()) => PlistErr::PLIST_ERR_SUCCESS,
+                        Err(e) => e,
+                    };
+                }
+                Err(e) => return e,
+            }
+        }
+        PlistErr::PLIST_ERR_SUCCESS
+    })
+}
+
+/// Removes the node at a path, handles to it turn invalid
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_path_remove_item(
+    plist: plist_t,
+    path: *const c_char,
+) -> plist_err_t {
+    ffi_guard(PlistErr::PLIST_ERR_UNKNOWN, || {
+        let Some(path) = (unsafe { borrow_str(path) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "path is null or not UTF-8");
+        };
+        if unsafe { PlistWrapper::from_handle(plist) }.is_none() {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        }
+        let segments = parse_path(path);
+        let Some((segment, parents)) = segments.split_last() else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "can't remove the root");
+        };
+        let parent = match resolve(plist, parents) {
+            Ok(parent) => parent,
+            Err(e) => return e,
+        };
+        let Some(wrapper) = (unsafe { PlistWrapper::from_handle(parent) }) else {
+            return fail(PlistErr::PLIST_ERR_INVALID_ARG, "invalid plist handle");
+        };
+        let missing = || {
+            fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                format!("{segment:?} doesn't exist"),
+            )
+        };
+        match wrapper.borrow_self() {
+            Some(Value::Dictionary(d)) => {
+                if d.remove(segment).is_none() {
+                    return missing();
+                }
+                wrapper.release_children(|_, k| k == Some(segment));
+            }
+            Some(Value::Array(a)) => {
+                let i = match array_index(segment) {
+                    Ok(i) => i,
+                    Err(e) => return e,
+                };
+                if i as usize >= a.len() {
+                    return missing();
+                }
+                a.remove(i as usize);
+                wrapper.reindex_children(i, false);
+            }
+            _ => {
+                return fail(
+                    PlistErr::PLIST_ERR_INVALID_ARG,
+                    format!("can't remove {segment:?}, the node isn't a dictionary or array"),
+                );
+            }
+        }
+        PlistErr::PLIST_ERR_SUCCESS
+    })
+}
+
+/// Finds the node a JSON Pointer, RFC 6901, like `/a/b~1c/0` refers to.
+/// The empty pointer is the node itself.
+/// Returns null if it doesn't exist, the reason is in plist_get_last_error.
+/// The node belongs to its parent, don't free it.
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_access_pointer(plist: plist_t, pointer: *const c_char) -> plist_t {
+    ffi_guard(std::ptr::null_mut(), || {
+        let Some(pointer) = (unsafe { borrow_str(pointer) }) else {
+            fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                "pointer is null or not UTF-8",
+            );
+            return std::ptr::null_mut();
+        };
+        let Some(tokens) = parse_pointer(pointer) else {
+            fail(
+                PlistErr::PLIST_ERR_INVALID_ARG,
+                format!("{pointer:?} isn't a JSON Pointer, it has to start with /"),
+            );
+            return std::ptr::null_mut();
+        };
+        if unsafe { PlistWrapper::from_handle(plist) }.is_none() {
+            return std::ptr::null_mut();
+        }
+        resolve(plist, &tokens).unwrap_or(std::ptr::null_mut())
+    })
+}
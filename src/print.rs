@@ -0,0 +1,272 @@
+// Jackson Coxson
+// The human readable output formats, modeled after libplist's out-default.c,
+// out-limd.c and out-plutil.c
+
+use std::fmt::Write;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use plist::{Date, Value};
+
+use crate::{PLIST_OPT_INDENT, PLIST_OPT_PARTIAL_DATA, PlistWriteOptions, null, openstep};
+
+/// How many levels PLIST_OPT_INDENT shifts the output by, kept in the top byte
+fn indent_levels(options: PlistWriteOptions) -> usize {
+    if options & PLIST_OPT_INDENT != 0 {
+        (options >> 24) as usize
+    } else {
+        0
+    }
+}
+
+/// Hex bytes in groups of four.
+/// Past 24 bytes only the first 16 and last 8 are written if `partial` is set.
+fn hex_groups(data: &[u8], partial: bool) -> String {
+    let group = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(String::new(), |mut out, (i, b)| {
+                if i > 0 && i % 4 == 0 {
+                    out.push(' ');
+                }
+                let _ = write!(out, "{b:02x}");
+                out
+            })
+    };
+    if partial && data.len() > 24 {
+        let tail = data.len() - 8;
+        format!("{} ... {}", group(&data[..16]), group(&data[tail..]))
+    } else {
+        group(data)
+    }
+}
+
+/// Dates the way strftime's `%Y-%m-%dT%H:%M:%SZ` writes them
+fn iso_date(date: &Date) -> String {
+    date.to_xml_format()
+}
+
+fn quote(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// PLIST_FORMAT_PRINT, what plist_print writes
+pub(crate) fn to_print(value: &Value, options: PlistWriteOptions) -> String {
+    let indent = indent_levels(options);
+    let mut out = "  ".repeat(indent);
+    print_node(
+        value,
+        &mut out,
+        indent,
+        options & PLIST_OPT_PARTIAL_DATA != 0,
+    );
+    out.push('\n');
+    out
+}
+
+fn print_node(value: &Value, out: &mut String, depth: usize, partial: bool) {
+    let line = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    match value {
+        Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        v if null::is_null(v) => out.push_str("null"),
+        Value::Integer(i) => {
+            let _ = write!(out, "{i}");
+        }
+        Value::Real(r) => out.push_str(&openstep::format_real(*r)),
+        Value::String(s) => quote(s, out),
+        Value::Array(a) => {
+            out.push('[');
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                line(out, depth + 1);
+                print_node(v, out, depth + 1, partial);
+            }
+            if !a.is_empty() {
+                line(out, depth);
+            }
+            out.push(']');
+        }
+        Value::Dictionary(d) => {
+            out.push('{');
+            for (i, (k, v)) in d.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                line(out, depth + 1);
+                quote(k, out);
+                out.push_str(": ");
+                print_node(v, out, depth + 1, partial);
+            }
+            if !d.is_empty() {
+                line(out, depth);
+            }
+            out.push('}');
+        }
+        Value::Data(d) => {
+            let _ = write!(out, "<{}>", hex_groups(d, partial));
+        }
+        Value::Date(d) => out.push_str(&iso_date(d)),
+        Value::Uid(u) => {
+            let _ = write!(out, "CF$UID:{}", u.get());
+        }
+        _ => {}
+    }
+}
+
+/// PLIST_FORMAT_LIMD, what the libimobiledevice tools like ideviceinfo print
+pub(crate) fn to_limd(value: &Value, options: PlistWriteOptions) -> String {
+    let mut out = String::new();
+    let indent = indent_levels(options);
+    match value {
+        Value::Dictionary(d) => limd_dict(d, &mut out, indent),
+        Value::Array(a) => limd_array(a, &mut out, indent),
+        v => limd_node(v, &mut out, indent),
+    }
+    out
+}
+
+fn limd_node(value: &Value, out: &mut String, indent: usize) {
+    match value {
+        Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        v if null::is_null(v) => out.push_str("null"),
+        Value::Integer(i) => {
+            let _ = write!(out, "{i}");
+        }
+        Value::Real(r) => {
+            let _ = write!(out, "{r:.6}");
+        }
+        Value::String(s) => out.push_str(s),
+        Value::Data(d) => out.push_str(&STANDARD.encode(d)),
+        Value::Date(d) => out.push_str(&iso_date(d)),
+        Value::Uid(u) => {
+            let _ = write!(out, "CF$UID:{}", u.get());
+        }
+        Value::Array(a) => {
+            out.push('\n');
+            limd_array(a, out, indent + 1);
+            return;
+        }
+        Value::Dictionary(d) => {
+            out.push('\n');
+            limd_dict(d, out, indent + 1);
+            return;
+        }
+        _ => {}
+    }
+    out.push('\n');
+}
+
+fn limd_array(a: &[Value], out: &mut String, indent: usize) {
+    for (i, v) in a.iter().enumerate() {
+        let _ = write!(out, "{:indent$}{i}: ", "");
+        limd_node(v, out, indent);
+    }
+}
+
+fn limd_dict(d: &plist::Dictionary, out: &mut String, indent: usize) {
+    for (k, v) in d {
+        let _ = write!(out, "{:indent$}{k}", "");
+        match v {
+            Value::Array(a) => {
+                let _ = write!(out, "[{}]: ", a.len());
+            }
+            _ => out.push_str(": "),
+        }
+        limd_node(v, out, indent);
+    }
+}
+
+/// PLIST_FORMAT_PLUTIL, what `plutil -p` prints.
+/// plutil has no indent option, so PLIST_OPT_INDENT leaves it as it is.
+pub(crate) fn to_plutil(value: &Value) -> String {
+    let mut out = String::new();
+    plutil_node(value, &mut out, 0);
+    out.push('\n');
+    out
+}
+
+fn plutil_node(value: &Value, out: &mut String, depth: usize) {
+    let pad = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+    match value {
+        // Booleans come out as the NSNumber they are to Foundation
+        Value::Boolean(b) => out.push_str(if *b { "1" } else { "0" }),
+        v if null::is_null(v) => out.push_str("null"),
+        Value::Integer(i) => {
+            let _ = write!(out, "{i}");
+        }
+        Value::Real(r) => {
+            let _ = write!(out, "{r}");
+        }
+        Value::String(s) => {
+            out.push('"');
+            out.push_str(&s.replace('\\', "\\\\").replace('"', "\\\""));
+            out.push('"');
+        }
+        Value::Array(a) => {
+            out.push_str("[\n");
+            for (i, v) in a.iter().enumerate() {
+                pad(out, depth + 1);
+                let _ = write!(out, "{i} => ");
+                plutil_node(v, out, depth + 1);
+                out.push('\n');
+            }
+            pad(out, depth);
+            out.push(']');
+        }
+        Value::Dictionary(d) => {
+            // plutil lists keys in order
+            let mut keys: Vec<&String> = d.keys().collect();
+            keys.sort();
+            out.push_str("{\n");
+            for k in keys {
+                pad(out, depth + 1);
+                let _ = write!(out, "\"{k}\" => ");
+                plutil_node(&d[k], out, depth + 1);
+                out.push('\n');
+            }
+            pad(out, depth);
+            out.push('}');
+        }
+        Value::Data(d) => {
+            // Like CFData's description, short data is one run of hex
+            let bytes = if d.len() <= 24 {
+                d.iter().fold(String::new(), |mut out, b| {
+                    let _ = write!(out, "{b:02x}");
+                    out
+                })
+            } else {
+                hex_groups(d, true)
+            };
+            let _ = write!(out, "{{length = {}, bytes = 0x{bytes}}}", d.len());
+        }
+        Value::Date(d) => {
+            // 2001-01-01T00:00:00Z becomes 2001-01-01 00:00:00 +0000
+            let date = iso_date(d).replacen('T', " ", 1).replace('Z', " +0000");
+            out.push_str(&date);
+        }
+        Value::Uid(u) => {
+            let _ = write!(out, "<CFKeyedArchiverUID>{{value = {}}}", u.get());
+        }
+        _ => {}
+    }
+}
@@ -1,83 +1,146 @@
 // Jackson Coxson
 
-use std::ffi::{CStr, c_char};
+use std::ffi::c_char;
 
 use plist::Value;
 
-use crate::plist_t;
+use crate::{
+    borrow_bytes, borrow_key, borrow_str, borrow_value, date, error, ffi_guard, handles, plist_t,
+};
 
+/// Only works on PLIST_KEY nodes.
+/// Renames the item in its dictionary like libplist does, if the key is taken
+/// nothing changes and it's reported through the last error.
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_key_val(node: plist_t, val: *const c_char) {
-    let node = unsafe { &mut *node }.borrow_self();
-    let val = unsafe { CStr::from_ptr(val) }.to_str().unwrap();
-    *node = Value::String(val.to_string());
+    ffi_guard((), || {
+        let (Some(key), Some(val)) = (unsafe { borrow_key(node) }, unsafe { borrow_str(val) })
+        else {
+            return;
+        };
+        // Key nodes belong to the wrapper of their item, copies belong to nobody
+        if let Some(item) = unsafe { handles::owner(node).as_mut() }
+            && let Err(e) = item.rename_item(val)
+        {
+            error::set_last_error(e);
+            return;
+        }
+        *key = val.to_string();
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_string_val(node: plist_t, val: *const c_char) {
-    let node = unsafe { &mut *node }.borrow_self();
-    let val = unsafe { CStr::from_ptr(val) }.to_str().unwrap();
-    *node = Value::String(val.to_string());
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) }
+            && let Some(val) = unsafe { borrow_str(val) }
+        {
+            *node = Value::String(val.to_string());
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_bool_val(node: plist_t, val: u8) {
-    let node = unsafe { &mut *node }.borrow_self();
-    *node = Value::Boolean(val != 0);
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Boolean(val != 0);
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_uint_val(node: plist_t, val: u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    *node = Value::Integer(val.into());
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Integer(val.into());
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_int_val(node: plist_t, val: i64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    *node = Value::Integer(val.into());
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Integer(val.into());
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_real_val(node: plist_t, val: f64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    *node = Value::Real(val);
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Real(val);
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_data_val(node: plist_t, val: *const u8, length: u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    let val = unsafe { std::slice::from_raw_parts(val, length as usize) }.to_vec();
-    *node = Value::Data(val);
+    ffi_guard((), || {
+        let Ok(length) = usize::try_from(length) else {
+            return;
+        };
+        if let Some(node) = unsafe { borrow_value(node) }
+            && let Some(val) = unsafe { borrow_bytes(val, length) }
+        {
+            *node = Value::Data(val.to_vec());
+        }
+    })
 }
 
+/// Sets seconds since 1970, negative for earlier dates
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_unix_date_val(node: plist_t, sec: i64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    let d = std::time::UNIX_EPOCH + std::time::Duration::from_secs(sec as u64);
-    *node = Value::Date(d.into());
+    ffi_guard((), || {
+        let Some(d) = date::from_unix_secs(sec) else {
+            return;
+        };
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Date(d);
+        }
+    })
+}
+
+/// Sets seconds and microseconds since the Mac epoch, 2001-01-01
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_set_date_val(node: plist_t, sec: i32, usec: i32) {
+    ffi_guard((), || {
+        let Some(d) = date::from_unix_nanos(date::mac_to_unix_nanos(sec, usec)) else {
+            return;
+        };
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Date(d);
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_set_uid_val(node: plist_t, val: u64) {
-    let node = unsafe { &mut *node }.borrow_self();
-    *node = Value::Uid(plist::Uid::new(val));
+    ffi_guard((), || {
+        if let Some(node) = unsafe { borrow_value(node) } {
+            *node = Value::Uid(plist::Uid::new(val));
+        }
+    })
 }
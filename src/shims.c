@@ -1,37 +1,35 @@
 // Jackson Coxson
 // We unfortunately must wait for VaList to be stabalized
+// Only the static library has these, a cdylib exports nothing but Rust's own symbols,
+// so plist.h leaves them out. Declare them yourself or use plist_access_path_elems.
 
 #include "../plist.h"
+#include <stdarg.h>
 #include <stdint.h>
-#include <stdlib.h>
-
-plist_t plist_access_path(plist_t plist, uint32_t length, ...) {
-  const void **path = malloc(sizeof(void *) * length);
-  if (!path)
-    return NULL;
-
-  va_list args;
-  va_start(args, length);
-  for (uint32_t i = 0; i < length; ++i) {
-    path[i] = va_arg(args, const void *);
-  }
-  va_end(args);
-
-  plist_t result = plist_access_path_shim(plist, length, path);
-  free(path);
-  return result;
-}
 
 plist_t plist_access_pathv(plist_t plist, uint32_t length, va_list v) {
-  const void **path = malloc(sizeof(void *) * length);
-  if (!path)
-    return NULL;
+  plist_t current = plist;
 
-  for (uint32_t i = 0; i < length; ++i) {
-    path[i] = va_arg(v, const void *);
+  // The node type decides whether the next argument is an index or a key
+  for (uint32_t i = 0; i < length && current; ++i) {
+    switch (plist_get_node_type(current)) {
+    case PLIST_ARRAY:
+      current = plist_array_get_item(current, va_arg(v, uint32_t));
+      break;
+    case PLIST_DICT:
+      current = plist_dict_get_item(current, va_arg(v, const char *));
+      break;
+    default:
+      current = NULL;
+    }
   }
+  return current;
+}
 
-  plist_t result = plist_access_path_shim(plist, length, path);
-  free(path);
+plist_t plist_access_path(plist_t plist, uint32_t length, ...) {
+  va_list args;
+  va_start(args, length);
+  plist_t result = plist_access_pathv(plist, length, args);
+  va_end(args);
   return result;
 }
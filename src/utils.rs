@@ -2,83 +2,65 @@
 
 use libc::size_t;
 use plist::Value;
-use std::ffi::{CStr, CString, c_char, c_void};
-
-use crate::{NodeType, PlistWrapper, plist_t};
-
+use std::{
+    cmp::Ordering,
+    ffi::{c_char, c_int, c_void},
+};
+
+use crate::{
+    PlistWrapper, borrow_bytes, borrow_key, borrow_str, borrow_value, date, ffi_guard, null,
+    plist_t,
+};
+
+/// One step of a path for plist_access_path_elems
+/// cbindgen:prefix-with-name
 #[repr(C)]
 pub enum PathElem {
+    /// A key into a dictionary
     Key(*const c_char),
+    /// An index into an array
     Index(u32),
 }
 
+/// Walks down a path of keys and indexes, the non-variadic plist_access_path.
+/// Returns null if a step doesn't exist or doesn't match the container it's used on.
+/// The returned node belongs to its parent, don't free it.
 /// # Safety
-/// Start praying
+/// Don't pass a bad plist >:(
+/// path must point to length elements
 #[unsafe(no_mangle)]
-#[cfg(feature = "danger")]
-pub unsafe extern "C" fn plist_access_path_shim(
+pub unsafe extern "C" fn plist_access_path_elems(
     plist: plist_t,
     length: u32,
-    path: *const *const c_void,
+    path: *const PathElem,
 ) -> plist_t {
-    if plist.is_null() || path.is_null() {
-        return std::ptr::null_mut();
-    }
-
-    let current = unsafe { &mut *plist };
-
-    for i in 0..length {
-        let arg = unsafe { *path.add(i as usize) };
-
-        match current.borrow_self() {
-            Value::Dictionary(d) => {
-                let key = arg as *const c_char;
-                if key.is_null() {
-                    return std::ptr::null_mut();
+    ffi_guard(std::ptr::null_mut(), || {
+        if path.is_null() && length > 0 {
+            return std::ptr::null_mut();
+        }
+        let mut current = plist;
+        for i in 0..length as usize {
+            let Some(wrapper) = (unsafe { PlistWrapper::from_handle(current) }) else {
+                return std::ptr::null_mut();
+            };
+            current = match (wrapper.borrow_self(), unsafe { &*path.add(i) }) {
+                (Some(Value::Dictionary(d)), PathElem::Key(key)) => {
+                    let Some(key) = (unsafe { borrow_str(*key) }) else {
+                        return std::ptr::null_mut();
+                    };
+                    if !d.contains_key(key) {
+                        return std::ptr::null_mut();
+                    }
+                    wrapper.child(u32::MAX, Some(key.to_string()))
                 }
-                let key_str = match unsafe { std::ffi::CStr::from_ptr(key).to_str() } {
-                    Ok(s) => s,
-                    Err(_) => return std::ptr::null_mut(),
-                };
-                let Some(val) = d.get_mut(key_str) else {
-                    return std::ptr::null_mut();
-                };
-                let mut new = PlistWrapper {
-                    node: NodeType::Child {
-                        node: val as *mut _,
-                        parent: current.borrow_self() as *mut _,
-                        index: u32::MAX,
-                        key: Some(key_str.to_string()),
-                    },
-                    children_wrappers: Vec::new(),
-                };
-                current
-                    .children_wrappers
-                    .push(&mut new as *mut PlistWrapper);
-            }
-            Value::Array(a) => {
-                let index = unsafe { *(arg as *const u32) };
-                let Some(val) = a.get_mut(index as usize) else {
-                    return std::ptr::null_mut();
-                };
-                let mut new = PlistWrapper {
-                    node: NodeType::Child {
-                        node: val as *mut _,
-                        parent: current.borrow_self() as *mut _,
-                        index,
-                        key: None,
-                    },
-                    children_wrappers: Vec::new(),
-                };
-                current
-                    .children_wrappers
-                    .push(&mut new as *mut PlistWrapper);
-            }
-            _ => return std::ptr::null_mut(),
+                (Some(Value::Array(a)), PathElem::Index(index)) if (*index as usize) < a.len() => {
+                    wrapper.child(*index, None)
+                }
+                _ => return std::ptr::null_mut(),
+            };
         }
-    }
-
-    current as *mut _
+        current
+    })
 }
 
 /// This function is extra silly and quirky and returns a char instead of an int for a bool
@@ -86,153 +68,237 @@ pub unsafe extern "C" fn plist_access_path_shim(
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_compare_node_value(node_l: plist_t, node_r: plist_t) -> c_char {
-    let l = unsafe { &mut *node_l }.borrow_self();
-    let r = unsafe { &mut *node_r }.borrow_self();
-    (l == r) as c_char
+    ffi_guard(0, || {
+        if let (Some(l), Some(r)) = (unsafe { borrow_key(node_l) }, unsafe { borrow_key(node_r) }) {
+            return (l == r) as c_char;
+        }
+        let (Some(l), Some(r)) = (unsafe { borrow_value(node_l) }, unsafe {
+            borrow_value(node_r)
+        }) else {
+            return 0;
+        };
+        (l == r) as c_char
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_bool_val_is_true(boolnode: plist_t) -> i8 {
-    let node = unsafe { &mut *boolnode }.borrow_self();
-    if let Value::Boolean(b) = node {
-        if *b { 1 } else { 0 }
-    } else {
-        0
-    }
+    ffi_guard(0, || {
+        if let Some(Value::Boolean(b)) = unsafe { borrow_value(boolnode) } {
+            if *b { 1 } else { 0 }
+        } else {
+            0
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_int_val_is_negative(intnode: plist_t) -> i8 {
-    let node = unsafe { &mut *intnode }.borrow_self();
-    if let Value::Integer(i) = node {
-        if *i < 0.into() { 1 } else { 0 }
-    } else {
-        0
-    }
+    ffi_guard(0, || {
+        if let Some(Value::Integer(i)) = unsafe { borrow_value(intnode) } {
+            if *i < 0.into() { 1 } else { 0 }
+        } else {
+            0
+        }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_int_val_compare(intnode: plist_t, cmpval: i64) -> i8 {
-    let node = unsafe { &mut *intnode }.borrow_self();
-    if let Value::Integer(i) = node {
-        let cmpval = cmpval.into();
-        if *i < cmpval {
-            -1
-        } else if *i > cmpval {
-            1
+    ffi_guard(-1, || {
+        if let Some(Value::Integer(i)) = unsafe { borrow_value(intnode) } {
+            let cmpval = cmpval.into();
+            if *i < cmpval {
+                -1
+            } else if *i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
-    } else {
-        -1
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_uint_val_compare(uintnode: plist_t, cmpval: u64) -> i8 {
-    let node = unsafe { &mut *uintnode }.borrow_self();
-    if let Value::Integer(i) = node {
-        let cmpval = cmpval.into();
-        if *i < cmpval {
-            -1
-        } else if *i > cmpval {
-            1
+    ffi_guard(-1, || {
+        if let Some(Value::Integer(i)) = unsafe { borrow_value(uintnode) } {
+            let cmpval = cmpval.into();
+            if *i < cmpval {
+                -1
+            } else if *i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
-    } else {
-        -1
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_uid_val_compare(uidtnode: plist_t, cmpval: u64) -> i8 {
-    let node = unsafe { &mut *uidtnode }.borrow_self();
-    if let Value::Uid(i) = node {
-        let i = i.get();
-        if i < cmpval {
-            -1
-        } else if i > cmpval {
-            1
+    ffi_guard(-1, || {
+        if let Some(Value::Uid(i)) = unsafe { borrow_value(uidtnode) } {
+            let i = i.get();
+            if i < cmpval {
+                -1
+            } else if i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
-    } else {
-        -1
-    }
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_real_val_compare(realnode: plist_t, cmpval: f64) -> i8 {
-    let node = unsafe { &mut *realnode }.borrow_self();
-    if let Value::Real(i) = node {
-        if *i < cmpval {
-            -1
-        } else if *i > cmpval {
-            1
+    ffi_guard(-1, || {
+        if let Some(Value::Real(i)) = unsafe { borrow_value(realnode) } {
+            if *i < cmpval {
+                -1
+            } else if *i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
-    } else {
-        -1
-    }
+    })
 }
 
+/// Compares against a Mac epoch date, microseconds included
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn plist_date_val_compare(datenode: plist_t, cmpval: i64) -> i8 {
-    let node = unsafe { &mut *datenode }.borrow_self();
-    if let Value::Date(i) = node {
-        let i: std::time::SystemTime = (*i).into();
-        let i = i.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
-        if i < cmpval {
-            -1
-        } else if i > cmpval {
-            1
+pub unsafe extern "C" fn plist_date_val_compare(
+    datenode: plist_t,
+    cmpsec: i32,
+    cmpusec: i32,
+) -> i8 {
+    ffi_guard(-1, || {
+        if let Some(v) = unsafe { borrow_value(datenode) }
+            && !null::is_null(v)
+            && let Value::Date(i) = v
+        {
+            let i = date::unix_nanos(*i);
+            let cmpval = date::mac_to_unix_nanos(cmpsec, cmpusec);
+            if i < cmpval {
+                -1
+            } else if i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
-    } else {
-        -1
-    }
+    })
 }
 
+/// Compares against a unix timestamp.
+/// A date with a fraction of a second sorts after the whole second it's in.
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn plist_string_val_compare(strnode: plist_t, cmpval: *const c_char) -> i8 {
-    let node = unsafe { &mut *strnode }.borrow_self();
-    let cmpval = unsafe { CStr::from_ptr(cmpval) }
-        .to_str()
-        .unwrap()
-        .to_string();
-
-    if let Value::String(i) = node {
-        if *i < cmpval {
-            -1
-        } else if *i > cmpval {
-            1
+pub unsafe extern "C" fn plist_unix_date_val_compare(datenode: plist_t, cmpval: i64) -> i8 {
+    ffi_guard(-1, || {
+        if let Some(v) = unsafe { borrow_value(datenode) }
+            && !null::is_null(v)
+            && let Value::Date(i) = v
+        {
+            let i = date::unix_nanos(*i);
+            let cmpval = cmpval as i128 * 1_000_000_000;
+            if i < cmpval {
+                -1
+            } else if i > cmpval {
+                1
+            } else {
+                0
+            }
         } else {
-            0
+            -1
         }
+    })
+}
+
+/// Borrows the string behind a string node
+unsafe fn string_of<'a>(node: plist_t) -> Option<&'a str> {
+    match unsafe { borrow_value(node) } {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+/// Borrows the string behind a key node
+unsafe fn key_of<'a>(node: plist_t) -> Option<&'a str> {
+    unsafe { borrow_key(node) }.map(|k| k.as_str())
+}
+
+fn str_val_compare(i: Option<&str>, cmpval: Option<&str>) -> i8 {
+    let (Some(i), Some(cmpval)) = (i, cmpval) else {
+        return -1;
+    };
+    if i < cmpval {
+        -1
+    } else if i > cmpval {
+        1
     } else {
+        0
+    }
+}
+
+fn str_val_compare_with_size(i: Option<&str>, cmpval: Option<&str>, n: size_t) -> i8 {
+    let (Some(i), Some(cmpval)) = (i, cmpval) else {
+        return -1;
+    };
+    // Compare bytes like strncmp, a char boundary at n isn't guaranteed
+    let i = &i.as_bytes()[..n.min(i.len())];
+    let cmpval = &cmpval.as_bytes()[..n.min(cmpval.len())];
+    if i < cmpval {
         -1
+    } else if i > cmpval {
+        1
+    } else {
+        0
+    }
+}
+
+fn str_val_contains(i: Option<&str>, substr: Option<&str>) -> i8 {
+    match (i, substr) {
+        (Some(i), Some(substr)) if i.contains(substr) => 1,
+        _ => 0,
     }
 }
 
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_string_val_compare(strnode: plist_t, cmpval: *const c_char) -> i8 {
+    ffi_guard(-1, || unsafe {
+        str_val_compare(string_of(strnode), borrow_str(cmpval))
+    })
+}
+
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
@@ -241,48 +307,31 @@ pub unsafe extern "C" fn plist_string_val_compare_with_size(
     cmpval: *const c_char,
     n: size_t,
 ) -> i8 {
-    let node = unsafe { &mut *strnode }.borrow_self();
-    let cmpval = unsafe { CStr::from_ptr(cmpval) }
-        .to_str()
-        .unwrap()
-        .to_string();
-
-    if let Value::String(i) = node {
-        let i = &i[..n];
-        let cmpval = &cmpval[..n];
-        if i < cmpval {
-            -1
-        } else if i > cmpval {
-            1
-        } else {
-            0
-        }
-    } else {
-        -1
-    }
+    ffi_guard(-1, || unsafe {
+        str_val_compare_with_size(string_of(strnode), borrow_str(cmpval), n)
+    })
 }
 
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_string_val_contains(strnode: plist_t, substr: *const c_char) -> i8 {
-    let node = unsafe { &mut *strnode }.borrow_self();
-    let substr = unsafe { CStr::from_ptr(substr) }.to_str().unwrap();
-
-    if let Value::String(i) = node {
-        if i.contains(substr) { 1 } else { 0 }
-    } else {
-        0
-    }
+    ffi_guard(0, || unsafe {
+        str_val_contains(string_of(strnode), borrow_str(substr))
+    })
 }
 
+/// Only accepts PLIST_KEY nodes
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_key_val_compare(keynode: plist_t, cmpval: *const c_char) -> i8 {
-    unsafe { plist_string_val_compare(keynode, cmpval) }
+    ffi_guard(-1, || unsafe {
+        str_val_compare(key_of(keynode), borrow_str(cmpval))
+    })
 }
 
+/// Only accepts PLIST_KEY nodes
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
@@ -291,14 +340,19 @@ pub unsafe extern "C" fn plist_key_val_compare_with_size(
     cmpval: *const c_char,
     n: size_t,
 ) -> i8 {
-    unsafe { plist_string_val_compare_with_size(keynode, cmpval, n) }
+    ffi_guard(-1, || unsafe {
+        str_val_compare_with_size(key_of(keynode), borrow_str(cmpval), n)
+    })
 }
 
+/// Only accepts PLIST_KEY nodes
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_key_val_contains(keynode: plist_t, substr: *const c_char) -> i8 {
-    unsafe { plist_string_val_contains(keynode, substr) }
+    ffi_guard(0, || unsafe {
+        str_val_contains(key_of(keynode), borrow_str(substr))
+    })
 }
 
 /// # Safety
@@ -309,14 +363,17 @@ pub unsafe extern "C" fn plist_data_val_compare(
     cmpval: *const u8,
     n: size_t,
 ) -> i8 {
-    let node = unsafe { &mut *datanode }.borrow_self();
-    let cmpval = unsafe { std::slice::from_raw_parts(cmpval, n) };
+    ffi_guard(0, || {
+        let Some(cmpval) = (unsafe { borrow_bytes(cmpval, n) }) else {
+            return 0;
+        };
 
-    if let Value::Data(i) = node {
-        if &i[..] > cmpval { 1 } else { 0 }
-    } else {
-        0
-    }
+        if let Some(Value::Data(i)) = unsafe { borrow_value(datanode) } {
+            if &i[..] > cmpval { 1 } else { 0 }
+        } else {
+            0
+        }
+    })
 }
 
 /// # Safety
@@ -327,14 +384,17 @@ pub unsafe extern "C" fn plist_data_val_compare_with_size(
     cmpval: *const u8,
     n: size_t,
 ) -> i8 {
-    let node = unsafe { &mut *datanode }.borrow_self();
-    let cmpval = unsafe { std::slice::from_raw_parts(cmpval, n) };
+    ffi_guard(0, || {
+        let Some(cmpval) = (unsafe { borrow_bytes(cmpval, n) }) else {
+            return 0;
+        };
 
-    if let Value::Data(i) = node {
-        if &i[..n] > cmpval { 1 } else { 0 }
-    } else {
-        0
-    }
+        if let Some(Value::Data(i)) = unsafe { borrow_value(datanode) } {
+            if &i[..n.min(i.len())] > cmpval { 1 } else { 0 }
+        } else {
+            0
+        }
+    })
 }
 
 // https://stackoverflow.com/questions/47043167/does-rust-contain-a-way-to-directly-check-whether-or-not-one-vector-is-a-substr
@@ -359,23 +419,217 @@ pub unsafe extern "C" fn plist_data_val_contains(
     cmpval: *const u8,
     n: size_t,
 ) -> i8 {
-    let node = unsafe { &mut *datanode }.borrow_self();
-    let cmpval = unsafe { std::slice::from_raw_parts(cmpval, n) };
+    ffi_guard(0, || {
+        let Some(cmpval) = (unsafe { borrow_bytes(cmpval, n) }) else {
+            return 0;
+        };
 
-    if let Value::Data(i) = node {
-        if is_sub(i, cmpval) { 1 } else { 0 }
-    } else {
-        0
-    }
+        if let Some(Value::Data(i)) = unsafe { borrow_value(datanode) } {
+            if is_sub(i, cmpval) { 1 } else { 0 }
+        } else {
+            0
+        }
+    })
 }
 
+/// Sorts the keys of every dictionary in the tree, arrays keep their order like libplist
 /// # Safety
 /// Don't pass a bad plist >:(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn plist_sort(plist: plist_t) {
-    let node = unsafe { &mut *plist }.borrow_self();
-    if let Value::Dictionary(d) = node {
-        d.sort_keys();
+    ffi_guard((), || {
+        if let Some(v) = unsafe { borrow_value(plist) } {
+            sort_keys(v);
+        }
+    })
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Dictionary(d) => {
+            d.sort_keys();
+            d.values_mut().for_each(sort_keys);
+        }
+        Value::Array(a) => a.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Orders two array elements for plist_sort_ex, negative, zero or positive like strcmp.
+/// The handles are only valid during the call.
+#[allow(non_camel_case_types)]
+pub type plist_sort_cb_t =
+    Option<unsafe extern "C" fn(a: plist_t, b: plist_t, user_data: *mut c_void) -> c_int>;
+
+/// plist_sort that also sorts arrays, with the comparator or by the value under `key`
+/// of the dictionaries in them, or both, in which case the comparator gets those values.
+/// Without a comparator values are ordered by type, then naturally.
+/// Elements without the key keep their order at the end.
+/// With neither this is plist_sort.
+/// # Safety
+/// Don't pass a bad plist >:(
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plist_sort_ex(
+    plist: plist_t,
+    compare: plist_sort_cb_t,
+    key: *const c_char,
+    user_data: *mut c_void,
+) {
+    ffi_guard((), || {
+        let key = unsafe { borrow_str(key) };
+        if compare.is_none() && key.is_none() {
+            return unsafe { plist_sort(plist) };
+        }
+        if let Some(wrapper) = unsafe { PlistWrapper::from_handle(plist) } {
+            sort_deep(wrapper, compare, key, user_data);
+        }
+    })
+}
+
+/// Walks through child wrappers, so handles the caller holds follow their element
+fn sort_deep(
+    wrapper: &mut PlistWrapper,
+    compare: plist_sort_cb_t,
+    key: Option<&str>,
+    user_data: *mut c_void,
+) {
+    let is_container = |v: &Value| matches!(v, Value::Dictionary(_) | Value::Array(_));
+    let children: Vec<plist_t> = match wrapper.borrow_self() {
+        Some(Value::Dictionary(d)) => {
+            d.sort_keys();
+            let keys: Vec<String> = d
+                .iter()
+                .filter(|(_, v)| is_container(v))
+                .map(|(k, _)| k.clone())
+                .collect();
+            keys.into_iter()
+                .map(|k| wrapper.child(u32::MAX, Some(k)))
+                .collect()
+        }
+        Some(Value::Array(a)) => {
+            let indexes: Vec<u32> = (0..a.len() as u32)
+                .filter(|i| is_container(&a[*i as usize]))
+                .collect();
+            indexes
+                .into_iter()
+                .map(|i| wrapper.child(i, None))
+                .collect()
+        }
+        _ => return,
+    };
+    for c in children {
+        if let Some(c) = unsafe { PlistWrapper::from_handle(c) } {
+            sort_deep(c, compare, key, user_data);
+        }
+    }
+    if matches!(wrapper.borrow_self(), Some(Value::Array(_))) {
+        sort_array(wrapper, compare, key, user_data);
+    }
+}
+
+fn sort_array(
+    wrapper: &mut PlistWrapper,
+    compare: plist_sort_cb_t,
+    key: Option<&str>,
+    user_data: *mut c_void,
+) {
+    let Some(Value::Array(a)) = wrapper.borrow_self() else {
+        return;
+    };
+    let has_key: Vec<bool> = a
+        .iter()
+        .map(|e| match (key, e) {
+            (None, _) => true,
+            (Some(key), Value::Dictionary(d)) => d.contains_key(key),
+            _ => false,
+        })
+        .collect();
+
+    // What gets compared, the element or the value under the key
+    let compared: Vec<Option<plist_t>> = (0..has_key.len() as u32)
+        .map(|i| {
+            if !has_key[i as usize] {
+                return None;
+            }
+            let element = wrapper.child(i, None);
+            match key {
+                None => Some(element),
+                Some(key) => unsafe { PlistWrapper::from_handle(element) }
+                    .map(|e| e.child(u32::MAX, Some(key.to_string()))),
+            }
+        })
+        .collect();
+
+    let mut order: Vec<u32> = (0..compared.len() as u32).collect();
+    order.sort_by(
+        |l, r| match (compared[*l as usize], compared[*r as usize]) {
+            (Some(l), Some(r)) => match compare {
+                Some(compare) => unsafe { compare(l, r, user_data) }.cmp(&0),
+                None => match (unsafe { borrow_value(l) }, unsafe { borrow_value(r) }) {
+                    (Some(l), Some(r)) => compare_values(l, r),
+                    _ => Ordering::Equal,
+                },
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    );
+
+    let Some(Value::Array(a)) = wrapper.borrow_self() else {
+        return;
+    };
+    let mut elements: Vec<Option<Value>> = std::mem::take(a).into_iter().map(Some).collect();
+    *a = order
+        .iter()
+        .filter_map(|i| elements[*i as usize].take())
+        .collect();
+    wrapper.permute_children(&order);
+}
+
+/// A total order for sorting, by type first and then by value
+fn compare_values(l: &Value, r: &Value) -> Ordering {
+    fn number(v: &Value) -> Option<f64> {
+        match v {
+            Value::Integer(i) => i
+                .as_signed()
+                .map(|i| i as f64)
+                .or_else(|| i.as_unsigned().map(|i| i as f64)),
+            Value::Real(f) => Some(*f),
+            _ => None,
+        }
+    }
+    fn rank(v: &Value) -> u8 {
+        match v {
+            v if null::is_null(v) => 8,
+            Value::Boolean(_) => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::String(_) => 2,
+            Value::Date(_) => 3,
+            Value::Data(_) => 4,
+            Value::Uid(_) => 5,
+            Value::Array(_) => 6,
+            Value::Dictionary(_) => 7,
+            _ => 9,
+        }
+    }
+    match (l, r) {
+        _ if null::is_null(l) || null::is_null(r) => rank(l).cmp(&rank(r)),
+        (Value::Boolean(l), Value::Boolean(r)) => l.cmp(r),
+        (Value::Integer(l), Value::Integer(r)) => match (l.as_signed(), r.as_signed()) {
+            (Some(l), Some(r)) => l.cmp(&r),
+            _ => l.as_unsigned().cmp(&r.as_unsigned()),
+        },
+        (Value::String(l), Value::String(r)) => l.cmp(r),
+        (Value::Date(l), Value::Date(r)) => date::unix_nanos(*l).cmp(&date::unix_nanos(*r)),
+        (Value::Data(l), Value::Data(r)) => l.cmp(r),
+        (Value::Uid(l), Value::Uid(r)) => l.get().cmp(&r.get()),
+        (Value::Array(l), Value::Array(r)) => l.len().cmp(&r.len()),
+        (Value::Dictionary(l), Value::Dictionary(r)) => l.len().cmp(&r.len()),
+        _ => match (number(l), number(r)) {
+            (Some(l), Some(r)) => l.total_cmp(&r),
+            _ => rank(l).cmp(&rank(r)),
+        },
     }
 }
 
@@ -386,5 +640,5 @@ pub extern "C" fn plist_set_debug(_debug: i8) {
 
 #[unsafe(no_mangle)]
 pub extern "C" fn libplist_version() -> *const c_char {
-    CString::new("2.0").unwrap().into_raw()
+    c"2.0".as_ptr()
 }
@@ -0,0 +1,82 @@
+// Jackson Coxson
+// XML written the way libplist's xplist.c writes it.
+// The plist crate escapes quotes and can't leave out line breaks.
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use plist::Value;
+
+use crate::{PlistErr, error::fail};
+
+const PROLOGUE: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd"><plist version="1.0">"#;
+
+/// Serializes a value into indented XML, without the final line break.
+/// libplist only escapes `<`, `>` and `&`, so quotes are put back.
+pub fn to_xml(value: &Value) -> Result<Vec<u8>, PlistErr> {
+    let mut out = Vec::new();
+    plist::to_writer_xml(&mut out, value).map_err(|e| {
+        fail(PlistErr::PLIST_ERR_UNKNOWN, format_args!("XML: {e}"))
+    })?;
+    // `&` is always written as `&amp;`, so these can only be escaped quotes
+    let text = String::from_utf8_lossy(&out)
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'");
+    Ok(text.into_bytes())
+}
+
+/// Serializes a value into XML without a single line break.
+/// UIDs can't be represented and return PLIST_ERR_FORMAT, null is up to the caller.
+pub fn to_compact_xml(value: &Value) -> Result<Vec<u8>, PlistErr> {
+    let mut out = PROLOGUE.to_vec();
+    write_node(value, &mut out)?;
+    out.extend_from_slice(b"</plist>");
+    Ok(out)
+}
+
+fn write_node(value: &Value, out: &mut Vec<u8>) -> Result<(), PlistErr> {
+    match value {
+        Value::Array(a) if a.is_empty() => out.extend_from_slice(b"<array/>"),
+        Value::Array(a) => {
+            out.extend_from_slice(b"<array>");
+            for v in a {
+                write_node(v, out)?;
+            }
+            out.extend_from_slice(b"</array>");
+        }
+        Value::Dictionary(d) if d.is_empty() => out.extend_from_slice(b"<dict/>"),
+        Value::Dictionary(d) => {
+            out.extend_from_slice(b"<dict>");
+            for (k, v) in d {
+                write_element("key", k, out);
+                write_node(v, out)?;
+            }
+            out.extend_from_slice(b"</dict>");
+        }
+        Value::Boolean(true) => out.extend_from_slice(b"<true/>"),
+        Value::Boolean(false) => out.extend_from_slice(b"<false/>"),
+        Value::Data(d) => write_element("data", &BASE64_STANDARD.encode(d), out),
+        Value::Date(d) => write_element("date", &d.to_xml_format(), out),
+        Value::Integer(i) => write_element("integer", &i.to_string(), out),
+        Value::Real(r) => write_element("real", &r.to_string(), out),
+        Value::String(s) => write_element("string", s, out),
+        _ => {
+            return Err(fail(
+                PlistErr::PLIST_ERR_FORMAT,
+                "XML can't represent a UID",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn write_element(name: &str, text: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("<{name}>").as_bytes());
+    for c in text.chars() {
+        match c {
+            '<' => out.extend_from_slice(b"&lt;"),
+            '>' => out.extend_from_slice(b"&gt;"),
+            '&' => out.extend_from_slice(b"&amp;"),
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    out.extend_from_slice(format!("</{name}>").as_bytes());
+}
@@ -0,0 +1,38 @@
+#include "test_helpers.h"
+
+static PathElem key(const char *k) {
+  PathElem e = {.tag = PathElem_Key, .key = k};
+  return e;
+}
+
+static PathElem index_(uint32_t i) {
+  PathElem e = {.tag = PathElem_Index, .index = i};
+  return e;
+}
+
+int main(int argc, char **argv) {
+  plist_t doc = json("{\"Devices\":[{\"UDID\":\"abc\"},{\"UDID\":\"def\"}],"
+                     "\"Count\":2}");
+
+  PathElem path[] = {key("Devices"), index_(1), key("UDID")};
+  plist_t udid = plist_access_path_elems(doc, 3, path);
+  check(is_string(udid, "def"), "walks keys and indexes");
+  plist_t devices = plist_dict_get_item(doc, "Devices");
+  check(udid == plist_dict_get_item(plist_array_get_item(devices, 1), "UDID"),
+        "gives the same handle as walking by hand");
+  check(plist_access_path_elems(doc, 0, NULL) == doc, "empty path is the root");
+
+  PathElem past_end[] = {key("Devices"), index_(2)};
+  check(!plist_access_path_elems(doc, 2, past_end), "index past the end");
+  PathElem missing[] = {key("Devices"), index_(0), key("Name")};
+  check(!plist_access_path_elems(doc, 3, missing), "missing key");
+  PathElem mismatched[] = {index_(0)};
+  check(!plist_access_path_elems(doc, 1, mismatched),
+        "index into a dictionary");
+  PathElem too_deep[] = {key("Count"), key("x")};
+  check(!plist_access_path_elems(doc, 2, too_deep), "key into an integer");
+  check(!plist_access_path_elems(doc, 1, NULL), "no path with a length");
+
+  plist_free(doc);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,87 @@
+#include "test_helpers.h"
+
+/* The 64-bit variant hands out the same bytes and length, null terminated */
+static void same(plist_err_t r32, char *b32, uint32_t l32, plist_err_t r64,
+                 char *b64, uint64_t l64, const char *what) {
+  check(r32 == PLIST_ERR_SUCCESS && r64 == PLIST_ERR_SUCCESS && b32 && b64 &&
+            l32 == l64 && !memcmp(b32, b64, l32) && b64[l64] == '\0',
+        what);
+  free(b32);
+  free(b64);
+}
+
+int main(int argc, char **argv) {
+  const char *text = "{\"name\":\"libplist\",\"list\":[1,2.5,\"x\"]}";
+  plist_t root = NULL;
+  plist_from_json64(text, strlen(text), &root);
+  check(plist_get_node_type(root) == PLIST_DICT, "from_json64");
+
+  char *b32 = NULL, *b64 = NULL;
+  uint32_t l32 = 0;
+  uint64_t l64 = 0;
+  plist_err_t r32, r64;
+
+  r32 = plist_to_xml(root, &b32, &l32);
+  r64 = plist_to_xml64(root, &b64, &l64);
+  same(r32, b32, l32, r64, b64, l64, "to_xml64");
+  r32 = plist_to_json(root, &b32, &l32, 1);
+  r64 = plist_to_json64(root, &b64, &l64, 1);
+  same(r32, b32, l32, r64, b64, l64, "to_json64");
+  r32 = plist_to_openstep(root, &b32, &l32, 0);
+  r64 = plist_to_openstep64(root, &b64, &l64, 0);
+  same(r32, b32, l32, r64, b64, l64, "to_openstep64");
+  r32 = plist_write_to_string(root, &b32, &l32, PLIST_FORMAT_LIMD, 0);
+  r64 = plist_write_to_string64(root, &b64, &l64, PLIST_FORMAT_LIMD, 0);
+  same(r32, b32, l32, r64, b64, l64, "write_to_string64");
+
+  char *bin = NULL;
+  uint64_t bin_len = 0;
+  plist_to_bin64(root, &bin, &bin_len);
+  check(plist_is_binary64(bin, bin_len) && !plist_is_binary64(bin, 7) &&
+            !plist_is_binary64(text, strlen(text)),
+        "is_binary64 goes by the header");
+
+  plist_t parsed = NULL;
+  enum PlistFormat format = PLIST_FORMAT_NONE;
+  check(plist_from_memory64(bin, bin_len, &parsed, &format) ==
+                PLIST_ERR_SUCCESS &&
+            format == PLIST_FORMAT_BINARY &&
+            plist_compare_node_value(root, parsed),
+        "from_memory64 of binary");
+  plist_free(parsed);
+  parsed = NULL;
+  check(plist_from_bin64(bin, bin_len, &parsed) == PLIST_ERR_SUCCESS &&
+            plist_compare_node_value(root, parsed),
+        "from_bin64");
+  plist_free(parsed);
+  free(bin);
+
+  /* only the given length is read, not up to a null */
+  const char *xml = "<plist><string>a</string></plist>trailing garbage";
+  parsed = NULL;
+  check(plist_from_xml64(xml, strlen(xml) - strlen("trailing garbage"),
+                         &parsed) == PLIST_ERR_SUCCESS &&
+            plist_get_node_type(parsed) == PLIST_STRING,
+        "from_xml64 stops at the length");
+  plist_free(parsed);
+  const char *ostep = "(a, b) and more";
+  parsed = NULL;
+  check(plist_from_openstep64(ostep, 6, &parsed) == PLIST_ERR_SUCCESS &&
+            plist_array_get_size(parsed) == 2,
+        "from_openstep64 stops at the length");
+  plist_free(parsed);
+
+  parsed = NULL;
+  const char *last = NULL;
+  check(plist_from_json64(NULL, 5, &parsed) == PLIST_ERR_INVALID_ARG &&
+            (last = plist_get_last_error()) &&
+            strstr(last, "input buffer is null"),
+        "null input with a length fails");
+  check(plist_to_xml64(root, &b64, NULL) == PLIST_ERR_INVALID_ARG &&
+            (last = plist_get_last_error()) &&
+            strstr(last, "output pointer is null"),
+        "null length pointer fails");
+
+  plist_free(root);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,123 @@
+#include "test_helpers.h"
+
+/* Seconds between 1970 and the Mac epoch, 2001-01-01 */
+#define MAC_EPOCH 978307200LL
+
+static int date_is(plist_t node, int32_t sec, int32_t usec) {
+  int32_t s = 0, u = -1;
+  plist_get_date_val(node, &s, &u);
+  return s == sec && u == usec;
+}
+
+static plist_t through_xml(plist_t node) {
+  char *out = NULL;
+  uint32_t size = 0;
+  plist_t back = NULL;
+  plist_to_xml(node, &out, &size);
+  plist_from_xml(out, size, &back);
+  free(out);
+  return back;
+}
+
+static plist_t through_bin(plist_t node) {
+  char *out = NULL;
+  uint32_t size = 0;
+  plist_t back = NULL;
+  plist_to_bin(node, &out, &size);
+  plist_from_bin(out, size, &back);
+  free(out);
+  return back;
+}
+
+static int64_t unix_secs(plist_t node) {
+  int64_t secs = 0;
+  plist_get_unix_date_val(node, &secs);
+  return secs;
+}
+
+/* Both round trips give back the same unix seconds */
+static void unix_round_trips(int64_t secs, const char *what) {
+  plist_t node = plist_new_unix_date(secs);
+  plist_t xml = through_xml(node);
+  plist_t bin = through_bin(node);
+  check(node && unix_secs(node) == secs && unix_secs(xml) == secs &&
+            unix_secs(bin) == secs,
+        what);
+  plist_free(node);
+  plist_free(xml);
+  plist_free(bin);
+}
+
+/* Both round trips give back the same seconds and microseconds */
+static void round_trips(int32_t sec, int32_t usec, const char *what) {
+  plist_t node = plist_new_date(sec, usec);
+  plist_t xml = through_xml(node);
+  plist_t bin = through_bin(node);
+  check(date_is(node, sec, usec) && date_is(xml, sec, usec) &&
+            date_is(bin, sec, usec),
+        what);
+  plist_free(node);
+  plist_free(xml);
+  plist_free(bin);
+}
+
+int main(int argc, char **argv) {
+  round_trips(700000000, 123456, "microseconds survive XML and binary");
+  round_trips(0, 1, "a single microsecond survives");
+  round_trips(0, 999999, "the last microsecond of a second survives");
+  round_trips(-700000000, 0, "whole seconds before the Mac epoch");
+
+  plist_t node = plist_new_date(0, 1500000);
+  check(date_is(node, 1, 500000), "microseconds carry into seconds");
+  plist_set_date_val(node, 700000000, 250000);
+  check(plist_get_node_type(node) == PLIST_DATE &&
+            date_is(node, 700000000, 250000),
+        "set keeps the microseconds");
+
+  check(plist_date_val_compare(node, 700000000, 250000) == 0,
+        "compare to the same microsecond");
+  check(plist_date_val_compare(node, 700000000, 249999) == 1,
+        "compare to the microsecond before");
+  check(plist_date_val_compare(node, 700000000, 250001) == -1,
+        "compare to the microsecond after");
+  check(plist_date_val_compare(node, 700000000, 0) == 1,
+        "compare to the whole second before");
+  check(plist_date_val_compare(node, 700000001, 0) == -1,
+        "compare to the whole second after");
+  check(plist_unix_date_val_compare(node, 700000000 + MAC_EPOCH) == 1,
+        "a fraction sorts after the unix second it's in");
+
+  check(unix_secs(node) == 700000000 + MAC_EPOCH,
+        "unix seconds drop the fraction");
+  plist_free(node);
+
+  unix_round_trips(-3153600000LL, "1870 survives XML and binary");
+  unix_round_trips(-1, "the second before 1970 survives");
+
+  node = plist_new_unix_date(-3153600000LL);
+  check(plist_unix_date_val_compare(node, -3153600000LL) == 0 &&
+            plist_unix_date_val_compare(node, -3153600001LL) == 1 &&
+            plist_unix_date_val_compare(node, 0) == -1,
+        "unix compare before 1970");
+  plist_free(node);
+
+  /* 1950, as far back as 32-bit Mac epoch seconds go is 1933 */
+  node = plist_new_unix_date(-631152000);
+  check(plist_date_val_compare(node, -631152000 - MAC_EPOCH, 0) == 0 &&
+            plist_date_val_compare(node, -631152000 - MAC_EPOCH, 1) == -1,
+        "Mac epoch compare before 1970");
+  check(date_is(node, -631152000 - MAC_EPOCH, 0),
+        "Mac epoch seconds before 1970");
+  plist_set_unix_date_val(node, -86400);
+  check(unix_secs(node) == -86400, "set a date before 1970");
+  plist_set_date_val(node, -MAC_EPOCH - 1, 500000);
+  check(unix_secs(node) == -1, "half a second before 1970 rounds down");
+  plist_free(node);
+
+  node = plist_new_unix_date(INT64_MIN);
+  check(node && unix_secs(node) == INT64_MIN &&
+            plist_unix_date_val_compare(node, INT64_MIN + 1) == -1,
+        "the earliest unix second");
+  plist_free(node);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
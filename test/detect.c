@@ -0,0 +1,59 @@
+#include "test_helpers.h"
+
+/* Parses with plist_from_memory and checks the format it reports */
+static plist_t from_memory(const char *data, enum PlistFormat expected,
+                           const char *what) {
+  plist_t node = NULL;
+  enum PlistFormat format = PLIST_FORMAT_NONE;
+  plist_err_t res = plist_from_memory(data, strlen(data), &node, &format);
+  check(res == PLIST_ERR_SUCCESS && format == expected, what);
+  return node;
+}
+
+int main(int argc, char **argv) {
+  plist_t expected = json("{\"a\":1}");
+  plist_t node = NULL;
+
+  node = from_memory("\xEF\xBB\xBF{\"a\":1}", PLIST_FORMAT_JSON,
+                     "JSON after a BOM");
+  check(plist_compare_node_value(node, expected), "BOM isn't part of the JSON");
+  plist_free(node);
+  node = from_memory("\xEF\xBB\xBF{ a = 1; }", PLIST_FORMAT_OSTEP,
+                     "OpenStep after a BOM");
+  check(plist_get_node_type(plist_dict_get_item(node, "a")) == PLIST_STRING,
+        "BOM isn't part of the OpenStep");
+  plist_free(node);
+  node = from_memory("\xEF\xBB\xBF<?xml version=\"1.0\"?>"
+                     "<plist version=\"1.0\"><dict><key>a</key>"
+                     "<integer>1</integer></dict></plist>",
+                     PLIST_FORMAT_XML, "XML after a BOM");
+  check(plist_compare_node_value(node, expected), "BOM isn't part of the XML");
+  plist_free(node);
+
+  plist_t direct = NULL;
+  plist_from_json("\xEF\xBB\xBF{\"a\":1}", 10, &direct);
+  check(plist_compare_node_value(direct, expected),
+        "plist_from_json skips a BOM");
+  plist_free(direct);
+  direct = NULL;
+  plist_from_openstep("\xEF\xBB\xBF{ a = 1; }", 13, &direct);
+  check(plist_get_node_type(direct) == PLIST_DICT,
+        "plist_from_openstep skips a BOM");
+  plist_free(direct);
+
+  char *bin = NULL;
+  uint32_t bin_len = 0;
+  plist_to_bin(expected, &bin, &bin_len);
+  check(plist_is_binary(bin, bin_len) == 1, "plist_is_binary on bplist00");
+  check(plist_is_binary64(bin, bin_len) == 1,
+        "plist_is_binary64 on bplist00");
+  check(plist_is_binary(bin, 7) == 0, "plist_is_binary wants all 8 bytes");
+  free(bin);
+  const char *xml = "<?xml version=\"1.0\"?><plist/>";
+  check(plist_is_binary(xml, strlen(xml)) == 0, "plist_is_binary on XML");
+  check(plist_is_binary("bp", 2) == 0, "plist_is_binary on short input");
+  check(plist_is_binary(NULL, 8) == 0, "plist_is_binary on NULL");
+
+  plist_free(expected);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,69 @@
+#include "test_helpers.h"
+
+/* Every failure has to leave its own message, not one from an earlier call */
+static void expect_error(plist_err_t res, const char *needle, const char *what) {
+  const char *last = plist_get_last_error();
+  if (res != PLIST_ERR_INVALID_ARG || !last || !strstr(last, needle)) {
+    printf("ERROR: %s returned %d with \"%s\"\n", what, res,
+           last ? last : "(null)");
+    err++;
+  } else {
+    printf("SUCCESS: %s\n", what);
+  }
+  /* leave something stale behind for the next check */
+  plist_t junk = NULL;
+  plist_from_json("{", 1, &junk);
+}
+
+int main(int argc, char **argv) {
+  plist_t source = plist_new_dict();
+  plist_t target = plist_new_dict();
+  plist_dict_set_item(source, "flag", plist_new_bool(1));
+  plist_dict_set_item(source, "name", plist_new_string("libplist"));
+  plist_dict_set_item(source, "count", plist_new_uint(7));
+
+  plist_t junk = NULL;
+  plist_from_json("{", 1, &junk);
+
+  expect_error(plist_dict_copy_item(target, source, "missing", NULL),
+               "\"missing\" doesn't exist", "copy_item of a missing key");
+  expect_error(plist_dict_copy_bool(target, source, "name", NULL),
+               "\"name\" isn't a boolean", "copy_bool of a string");
+  expect_error(plist_dict_copy_int(target, source, "flag", NULL),
+               "\"flag\" isn't an integer", "copy_int of a boolean");
+  expect_error(plist_dict_copy_uint(target, source, "x", "flag"),
+               "\"flag\" isn't an unsigned integer", "copy_uint of a boolean");
+  expect_error(plist_dict_copy_data(target, source, "count", NULL),
+               "\"count\" isn't data", "copy_data of an integer");
+  expect_error(plist_dict_copy_string(target, source, "count", NULL),
+               "\"count\" isn't a string", "copy_string of an integer");
+  expect_error(plist_dict_copy_string(target, source, NULL, NULL),
+               "key is null", "copy_string with a null key");
+  expect_error(plist_dict_copy_item(target, plist_new_array(), "name", NULL),
+               "source isn't a dictionary", "copy_item from an array");
+
+  char *title = NULL;
+  plist_err_t res = plist_dict_copy_string(target, source, "title", "name");
+  plist_get_string_val(plist_dict_get_item(target, "title"), &title);
+  if (res != PLIST_ERR_SUCCESS || !title || strcmp(title, "libplist") != 0) {
+    printf("ERROR: copy_string with alt_source_key\n");
+    err++;
+  } else {
+    printf("SUCCESS: copy_string with alt_source_key\n");
+  }
+  free(title);
+
+  /* copying within one dictionary */
+  if (plist_dict_copy_uint(source, source, "again", "count") !=
+          PLIST_ERR_SUCCESS ||
+      plist_dict_get_uint(source, "again") != 7) {
+    printf("ERROR: copy_uint within one dictionary\n");
+    err++;
+  } else {
+    printf("SUCCESS: copy_uint within one dictionary\n");
+  }
+
+  plist_free(source);
+  plist_free(target);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
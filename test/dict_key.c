@@ -0,0 +1,68 @@
+#include "test_helpers.h"
+
+static int key_is(plist_t key, const char *expected) {
+  char *val = NULL;
+  plist_get_key_val(key, &val);
+  int ok = val && strcmp(val, expected) == 0;
+  free(val);
+  return ok;
+}
+
+/* The keys of the dictionary in order, joined with commas */
+static int keys_are(plist_t dict, const char *expected) {
+  char joined[256] = "";
+  plist_dict_iter it = NULL;
+  char *k = NULL;
+  plist_dict_new_iter(dict, &it);
+  for (plist_dict_next_item(dict, it, &k, NULL); k;
+       plist_dict_next_item(dict, it, &k, NULL)) {
+    if (joined[0]) {
+      strcat(joined, ",");
+    }
+    strcat(joined, k);
+    free(k);
+    k = NULL;
+  }
+  plist_dict_free_iter(it);
+  return strcmp(joined, expected) == 0;
+}
+
+int main(int argc, char **argv) {
+  plist_t dict = plist_new_dict();
+  plist_dict_set_item(dict, "a", plist_new_uint(1));
+  plist_dict_set_item(dict, "b", plist_new_uint(2));
+  plist_dict_set_item(dict, "c", plist_new_uint(3));
+
+  plist_t item = plist_dict_get_item(dict, "b");
+  plist_t key = plist_dict_item_get_key(item);
+  check(plist_get_node_type(key) == PLIST_KEY, "item key is a PLIST_KEY");
+
+  plist_set_key_val(key, "z");
+  check(key_is(key, "z"), "key node has the new name");
+  check(keys_are(dict, "a,z,c"), "item is renamed in place");
+  check(!plist_dict_get_item(dict, "b"), "old key is gone");
+  check(plist_dict_get_item(dict, "z") == item,
+        "item handle follows the rename");
+  check(plist_dict_item_get_key(item) == key, "key handle follows the rename");
+  uint64_t val = 0;
+  plist_get_uint_val(item, &val);
+  check(val == 2, "item keeps its value");
+
+  plist_clear_last_error();
+  plist_set_key_val(key, "a");
+  const char *last = plist_get_last_error();
+  check(last && strstr(last, "\"a\" is already a key"),
+        "renaming onto a taken key fails");
+  check(key_is(key, "z") && keys_are(dict, "a,z,c") &&
+            plist_dict_get_uint(dict, "a") == 1,
+        "failed rename changes nothing");
+
+  plist_t copy = plist_copy(key);
+  plist_set_key_val(copy, "a");
+  check(key_is(copy, "a") && keys_are(dict, "a,z,c"),
+        "renaming a copied key leaves the dictionary alone");
+  plist_free(copy);
+
+  plist_free(dict);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
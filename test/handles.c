@@ -0,0 +1,86 @@
+#include "test_helpers.h"
+
+/* A handle that was freed along with its element is turned away */
+static int is_invalid(plist_t node) {
+  plist_clear_last_error();
+  const char *last = NULL;
+  return plist_get_node_type(node) == PLIST_NONE &&
+         (last = plist_get_last_error()) &&
+         strstr(last, "invalid plist handle");
+}
+
+static int descending(plist_t a, plist_t b, void *user_data) {
+  uint64_t l = uint_of(a), r = uint_of(b);
+  return (l < r) - (l > r);
+}
+
+int main(int argc, char **argv) {
+  plist_t array = plist_new_array();
+  for (uint64_t i = 0; i < 4; i++) {
+    plist_array_append_item(array, plist_new_uint(i));
+  }
+  plist_t one = plist_array_get_item(array, 1);
+  plist_t three = plist_array_get_item(array, 3);
+  check(plist_array_get_item(array, 1) == one,
+        "repeated lookups hand out the same handle");
+
+  plist_array_insert_item(array, plist_new_uint(9), 0);
+  check(uint_of(one) == 1 && plist_array_get_item_index(one) == 2,
+        "handle follows its element past an insert");
+  check(plist_array_get_item(array, 2) == one,
+        "lookup at the new index finds it");
+
+  /* enough to make the array reallocate */
+  for (uint64_t i = 0; i < 1000; i++) {
+    plist_array_append_item(array, plist_new_uint(100 + i));
+  }
+  check(uint_of(one) == 1 && uint_of(three) == 3,
+        "handles survive the array growing");
+
+  plist_array_remove_item(array, 0);
+  check(uint_of(one) == 1 && plist_array_get_item_index(one) == 1,
+        "handle follows its element past a removal");
+
+  plist_array_remove_item(array, 1);
+  check(is_invalid(one), "handle to a removed element turns invalid");
+  check(uint_of(plist_array_get_item(array, 1)) == 2,
+        "the next element moves up");
+
+  plist_array_item_remove(three);
+  check(plist_array_get_size(array) == 1002 &&
+            uint_of(plist_array_get_item(array, 2)) == 100,
+        "item_remove takes out its own element");
+
+  /* drop the appended elements again, then sort what's left */
+  while (plist_array_get_size(array) > 2) {
+    plist_array_remove_item(array, 2);
+  }
+  plist_t zero = plist_array_get_item(array, 0);
+  plist_t two = plist_array_get_item(array, 1);
+  plist_sort_ex(array, descending, NULL, NULL);
+  check(two == plist_array_get_item(array, 0) &&
+            zero == plist_array_get_item(array, 1) &&
+            plist_array_get_item_index(zero) == 1 && uint_of(two) == 2,
+        "handles follow their elements through a sort");
+
+  plist_t dict = plist_new_dict();
+  plist_dict_set_item(dict, "a", plist_new_uint(1));
+  plist_t a = plist_dict_get_item(dict, "a");
+  for (int i = 0; i < 100; i++) {
+    char key[8];
+    snprintf(key, sizeof(key), "k%d", i);
+    plist_dict_set_item(dict, key, plist_new_uint(i));
+  }
+  check(uint_of(a) == 1 && plist_dict_get_item(dict, "a") == a,
+        "dictionary handle survives other keys being added");
+  plist_dict_remove_item(dict, "k0");
+  check(uint_of(a) == 1,
+        "dictionary handle survives another key being removed");
+  plist_dict_set_item(dict, "a", plist_new_uint(2));
+  check(is_invalid(a), "handle to a replaced item turns invalid");
+  check(plist_dict_get_uint(dict, "a") == 2, "replacement is in place");
+
+  plist_free(array);
+  plist_free(dict);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
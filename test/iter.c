@@ -0,0 +1,58 @@
+#include "test_helpers.h"
+
+int main(int argc, char **argv) {
+  plist_t array = json("[1, \"two\", [], {\"k\": 4}]");
+  plist_array_iter it = NULL;
+  plist_t item = NULL;
+  uint32_t seen = 0;
+  int same = 1;
+
+  plist_array_new_iter(array, &it);
+  check(it != NULL, "plist_array_new_iter hands out an iterator");
+  for (plist_array_next_item(array, it, &item); item;
+       plist_array_next_item(array, it, &item)) {
+    same &= item == plist_array_get_item(array, seen);
+    seen++;
+  }
+  check(seen == 4, "plist_array_next_item visits every item");
+  check(same, "each item is the handle plist_array_get_item returns");
+
+  item = array;
+  plist_array_next_item(array, it, &item);
+  check(item == NULL, "a finished iterator keeps answering NULL");
+  plist_array_free_iter(it);
+
+  plist_array_new_iter(array, &it);
+  plist_array_next_item(array, it, &item);
+  check(uint_of(item) == 1, "a new iterator starts from the first item");
+  plist_array_free_iter(it);
+
+  plist_t empty = plist_new_array();
+  plist_array_new_iter(empty, &it);
+  item = array;
+  plist_array_next_item(empty, it, &item);
+  check(item == NULL, "an empty array has nothing to iterate");
+  plist_array_free_iter(it);
+  plist_free(empty);
+
+  plist_array_free_iter(array);
+  check(strstr(plist_get_last_error(), "use plist_free") != NULL,
+        "plist_array_free_iter refuses to free a node");
+  check(plist_array_get_size(array) == 4, "the refused node is still there");
+
+  plist_t dict = plist_array_get_item(array, 3);
+  plist_dict_iter dit = NULL;
+  char *key = NULL;
+  plist_dict_new_iter(dict, &dit);
+  plist_dict_next_item(dict, dit, &key, &item);
+  check(key && strcmp(key, "k") == 0 && uint_of(item) == 4,
+        "plist_dict_next_item hands out the key and value");
+  free(key);
+  key = NULL;
+  plist_dict_next_item(dict, dit, &key, &item);
+  check(key == NULL && item == NULL, "the dict iterator ends with NULL");
+  plist_dict_free_iter(dit);
+
+  plist_free(array);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,107 @@
+#include "test_helpers.h"
+
+static const char *TARGET =
+    "{\"name\":\"a\",\"list\":[1],\"conf\":1,"
+    "\"nested\":{\"x\":1,\"keep\":true,\"list\":[2]}}";
+static const char *SOURCE =
+    "{\"name\":\"b\",\"list\":[3],\"conf\":2,\"added\":1,"
+    "\"nested\":{\"x\":2,\"list\":[4],\"new\":5}}";
+
+static void expect(plist_t target, const char *result, const char *what) {
+  plist_t expected = json(result);
+  check(plist_compare_node_value(target, expected), what);
+  plist_free(expected);
+}
+
+static void merge(uint32_t strategy, plist_merge_cb_t callback,
+                  void *user_data, const char *result, const char *what) {
+  plist_t target = json(TARGET);
+  plist_t source = json(SOURCE);
+  if (plist_dict_merge_deep(&target, source, strategy, callback, user_data) !=
+      PLIST_ERR_SUCCESS) {
+    printf("ERROR: %s failed: %s\n", what, plist_get_last_error());
+    err++;
+  } else {
+    expect(target, result, what);
+  }
+  plist_free(target);
+  plist_free(source);
+}
+
+/* Concatenates lists, overwrites names, bumps x in place and keeps the rest */
+static uint32_t decide(const char *key, plist_t target, plist_t source,
+                       void *user_data) {
+  (*(int *)user_data)++;
+  if (!strcmp(key, "list")) {
+    return PLIST_MERGE_CONCAT_ARRAYS;
+  }
+  if (!strcmp(key, "name")) {
+    return PLIST_MERGE_OVERWRITE;
+  }
+  if (!strcmp(key, "x")) {
+    uint64_t t = 0, s = 0;
+    plist_get_uint_val(target, &t);
+    plist_get_uint_val(source, &s);
+    plist_set_uint_val(target, t * 100 + s);
+  }
+  return PLIST_MERGE_KEEP;
+}
+
+/* Answers with something that isn't a strategy */
+static uint32_t garbage(const char *key, plist_t target, plist_t source,
+                        void *user_data) {
+  return 42;
+}
+
+int main(int argc, char **argv) {
+  merge(PLIST_MERGE_KEEP, NULL, NULL,
+        "{\"name\":\"a\",\"list\":[1],\"conf\":1,\"added\":1,"
+        "\"nested\":{\"x\":1,\"keep\":true,\"list\":[2],\"new\":5}}",
+        "keep merges nested dictionaries and keeps conflicts");
+  merge(PLIST_MERGE_OVERWRITE, NULL, NULL,
+        "{\"name\":\"b\",\"list\":[3],\"conf\":2,\"added\":1,"
+        "\"nested\":{\"x\":2,\"keep\":true,\"list\":[4],\"new\":5}}",
+        "overwrite replaces conflicts at every level");
+  merge(PLIST_MERGE_CONCAT_ARRAYS, NULL, NULL,
+        "{\"name\":\"b\",\"list\":[1,3],\"conf\":2,\"added\":1,"
+        "\"nested\":{\"x\":2,\"keep\":true,\"list\":[2,4],\"new\":5}}",
+        "concat arrays appends at every level and overwrites the rest");
+
+  int calls = 0;
+  merge(PLIST_MERGE_CALLBACK, decide, &calls,
+        "{\"name\":\"b\",\"list\":[1,3],\"conf\":1,\"added\":1,"
+        "\"nested\":{\"x\":102,\"keep\":true,\"list\":[2,4],\"new\":5}}",
+        "callback decides each conflict");
+  check(calls == 5, "callback only sees conflicts that aren't dictionaries");
+
+  plist_t target = json(TARGET);
+  plist_t source = json(SOURCE);
+  check(plist_dict_merge_deep(&target, source, PLIST_MERGE_CALLBACK, NULL,
+                              NULL) == PLIST_ERR_INVALID_ARG,
+        "callback strategy without a callback fails");
+  check_error(plist_dict_merge_deep(&target, source, 9, NULL, NULL),
+              PLIST_ERR_INVALID_ARG, "9 isn't a merge strategy",
+              "unknown strategy fails");
+  expect(target, TARGET, "unknown strategy leaves the target alone");
+  plist_t copy = plist_copy(target);
+  check_error(plist_dict_merge_deep(&copy, source, PLIST_MERGE_CALLBACK,
+                                    garbage, NULL),
+              PLIST_ERR_INVALID_ARG, "answered 42, which isn't a merge",
+              "unknown answer from the callback fails");
+  plist_free(copy);
+
+  /* merging a child of another tree, through a handle held on the target */
+  plist_t nested = plist_dict_get_item(target, "nested");
+  plist_t from = plist_dict_get_item(source, "nested");
+  check(plist_dict_merge_deep(&nested, from, PLIST_MERGE_OVERWRITE, NULL,
+                              NULL) == PLIST_ERR_SUCCESS,
+        "source can be a child");
+  check(plist_dict_get_item(target, "nested") == nested &&
+            plist_dict_get_uint(nested, "new") == 5,
+        "target handle sees the merge");
+  expect(source, SOURCE, "source is left alone");
+
+  plist_free(target);
+  plist_free(source);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,45 @@
+#include "test_helpers.h"
+
+int main(int argc, char **argv) {
+  plist_t node = plist_new_null();
+  check(plist_get_node_type(node) == PLIST_NULL,
+        "plist_new_null is PLIST_NULL");
+
+  plist_t copy = plist_copy(node);
+  check(plist_get_node_type(copy) == PLIST_NULL &&
+            plist_compare_node_value(node, copy),
+        "a copy is an equal null");
+  plist_free(copy);
+
+  int64_t secs = 42;
+  plist_get_unix_date_val(node, &secs);
+  check(secs == 42 && plist_unix_date_val_compare(node, 0) == -1,
+        "a null isn't a date");
+
+  char *out = NULL;
+  uint32_t size = 0;
+  check(plist_to_json(node, &out, &size, 0) == PLIST_ERR_SUCCESS && out &&
+            strcmp(out, "null") == 0,
+        "JSON writes null");
+  free(out);
+  out = NULL;
+  check(plist_to_xml(node, &out, &size) == PLIST_ERR_FORMAT && !out,
+        "XML can't hold a null");
+
+  plist_t dict = plist_new_dict();
+  plist_dict_set_item(dict, "n", node);
+  plist_dict_set_item(dict, "uid", plist_new_uid(UINT64_MAX));
+  plist_to_bin(dict, &out, &size);
+  plist_t back = NULL;
+  plist_from_bin(out, size, &back);
+  free(out);
+  check(plist_get_node_type(plist_dict_get_item(back, "n")) == PLIST_NULL &&
+            plist_get_node_type(plist_dict_get_item(back, "uid")) ==
+                PLIST_UID,
+        "binary keeps a null in a dictionary apart from a UID");
+  check(plist_compare_node_value(dict, back), "binary round trip");
+  plist_free(back);
+  plist_free(dict);
+
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
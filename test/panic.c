@@ -0,0 +1,34 @@
+#include "test_helpers.h"
+
+/* Past the year 9999 the time crate panics while formatting the date */
+#define FAR_FUTURE 1000000000000000LL
+
+int main(int argc, char **argv) {
+  plist_t date = plist_new_unix_date(FAR_FUTURE);
+  plist_t array = plist_new_array();
+  plist_array_append_item(array, plist_copy(date));
+  char *out = NULL;
+  uint32_t length = 0;
+
+  plist_err_t res = plist_to_xml(date, &out, &length);
+  check(res == PLIST_ERR_UNKNOWN && out == NULL,
+        "a panic in plist_to_xml comes back as PLIST_ERR_UNKNOWN");
+  check(strstr(plist_get_last_error(), "internal error") != NULL,
+        "the panic message ends up in plist_get_last_error");
+
+  res = plist_to_json(array, &out, &length, 0);
+  check(res == PLIST_ERR_UNKNOWN && out == NULL,
+        "a panic below the root is caught too");
+
+  int64_t secs = 0;
+  plist_get_unix_date_val(date, &secs);
+  check(secs == FAR_FUTURE, "the date survives the panic");
+  plist_set_unix_date_val(date, 0);
+  res = plist_to_xml(date, &out, &length);
+  check(res == PLIST_ERR_SUCCESS, "the library keeps working afterwards");
+  free(out);
+
+  plist_free(array);
+  plist_free(date);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
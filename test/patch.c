@@ -0,0 +1,96 @@
+#include "test_helpers.h"
+
+/* Applies the ops and checks the result, freeing the ops */
+static void expect(plist_t doc, const char *ops, plist_err_t res,
+                   const char *result, const char *what) {
+  plist_t patch = json(ops);
+  plist_t expected = json(result);
+  plist_err_t r = plist_patch(doc, patch);
+  if (r != res || !plist_compare_node_value(doc, expected)) {
+    char *out = NULL;
+    uint32_t size = 0;
+    plist_to_json(doc, &out, &size, 0);
+    printf("ERROR: %s returned %d with %s\n", what, r, out ? out : "(null)");
+    free(out);
+    err++;
+  } else {
+    printf("SUCCESS: %s\n", what);
+  }
+  plist_free(patch);
+  plist_free(expected);
+}
+
+int main(int argc, char **argv) {
+  plist_t doc = json("{\"a\":{\"b/c\":1,\"~k\":2},\"list\":[1,2,3]}");
+
+  expect(doc,
+         "[{\"op\":\"add\",\"path\":\"/list/1\",\"value\":9},"
+         "{\"op\":\"add\",\"path\":\"/list/-\",\"value\":4},"
+         "{\"op\":\"add\",\"path\":\"/new\",\"value\":true}]",
+         PLIST_ERR_SUCCESS,
+         "{\"a\":{\"b/c\":1,\"~k\":2},\"list\":[1,9,2,3,4],\"new\":true}",
+         "add into an array, onto its end and into a dictionary");
+
+  expect(doc,
+         "[{\"op\":\"remove\",\"path\":\"/list/0\"},"
+         "{\"op\":\"remove\",\"path\":\":new\"}]",
+         PLIST_ERR_SUCCESS, "{\"a\":{\"b/c\":1,\"~k\":2},\"list\":[9,2,3,4]}",
+         "remove with a JSON Pointer and a PlistBuddy path");
+
+  expect(doc,
+         "[{\"op\":\"move\",\"from\":\"/a/b~1c\",\"path\":\"/a/~0moved\"},"
+         "{\"op\":\"move\",\"from\":\"/a/~0k\",\"path\":\"/k\"}]",
+         PLIST_ERR_SUCCESS,
+         "{\"a\":{\"~moved\":1},\"list\":[9,2,3,4],\"k\":2}",
+         "move with ~0 and ~1 escapes");
+
+  expect(doc, "[{\"op\":\"copy\",\"from\":\"/list\",\"path\":\"/a/copy\"}]",
+         PLIST_ERR_SUCCESS,
+         "{\"a\":{\"~moved\":1,\"copy\":[9,2,3,4]},\"list\":[9,2,3,4],\"k\":2}",
+         "copy");
+
+  expect(doc,
+         "[{\"op\":\"test\",\"path\":\"/k\",\"value\":2},"
+         "{\"op\":\"replace\",\"path\":\"/k\",\"value\":3}]",
+         PLIST_ERR_SUCCESS,
+         "{\"a\":{\"~moved\":1,\"copy\":[9,2,3,4]},\"list\":[9,2,3,4],\"k\":3}",
+         "passing test");
+
+  plist_clear_last_error();
+  expect(doc,
+         "[{\"op\":\"remove\",\"path\":\"/a\"},"
+         "{\"op\":\"replace\",\"path\":\"/list/0\",\"value\":0},"
+         "{\"op\":\"test\",\"path\":\"/k\",\"value\":42}]",
+         PLIST_ERR_INVALID_ARG,
+         "{\"a\":{\"~moved\":1,\"copy\":[9,2,3,4]},\"list\":[9,2,3,4],\"k\":3}",
+         "failing test rolls every operation back");
+  const char *last = plist_get_last_error();
+  if (!last || !strstr(last, "operation 2: test failed")) {
+    printf("ERROR: failing test reported \"%s\"\n", last ? last : "(null)");
+    err++;
+  }
+
+  expect(doc, "[{\"op\":\"remove\",\"path\":\"/list/9\"}]",
+         PLIST_ERR_INVALID_ARG,
+         "{\"a\":{\"~moved\":1,\"copy\":[9,2,3,4]},\"list\":[9,2,3,4],\"k\":3}",
+         "out of bounds index fails");
+  plist_free(doc);
+
+  /* the paths of a diff name the empty key, not the root */
+  plist_t old = json("{\"\":1,\"x\":{\"\":2}}");
+  plist_t new_ = json("{\"\":5,\"x\":{\"\":3}}");
+  plist_t changes = NULL;
+  plist_diff(old, new_, &changes);
+  if (plist_patch(old, changes) != PLIST_ERR_SUCCESS ||
+      !plist_compare_node_value(old, new_)) {
+    printf("ERROR: diff with empty keys patches back\n");
+    err++;
+  } else {
+    printf("SUCCESS: diff with empty keys patches back\n");
+  }
+  plist_free(changes);
+  plist_free(old);
+  plist_free(new_);
+
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,80 @@
+#include "test_helpers.h"
+
+int main(int argc, char **argv) {
+  plist_t doc = json("{\"Devices\":[{\"UDID\":\"abc\"}],\"a:b\":1,"
+                     "\"\":{\"\":7}}");
+  plist_t item = NULL;
+  plist_t other = NULL;
+
+  plist_path_get_item(doc, ":Devices:0:UDID", &item);
+  plist_path_get_item(doc, "/Devices/0/UDID", &other);
+  check(is_string(item, "abc") && item == other,
+        "get with either separator finds the same node");
+  plist_path_get_item(doc, ":a\\:b", &item);
+  check(plist_get_node_type(item) == PLIST_INT, "get an escaped separator");
+  plist_path_get_item(doc, "", &item);
+  plist_path_get_item(doc, ":", &other);
+  check(item == doc && other == doc, "empty path and a lone : are the root");
+  plist_path_get_item(doc, ":Devices::0:", &item);
+  check(plist_get_node_type(item) == PLIST_DICT, "empty components are skipped");
+  plist_path_get_item(doc, ":\"\":\"\"", &item);
+  plist_path_get_item(doc, "/\"\"/\"\"", &other);
+  check(plist_get_node_type(item) == PLIST_INT && item == other,
+        "\"\" is the empty key");
+  check_error(plist_path_get_item(doc, ":\\\"\"", &item),
+              PLIST_ERR_INVALID_ARG, "\"\\\"\\\"\" doesn't exist",
+              "an escaped \"\" is two quotes");
+
+  check_error(plist_path_get_item(doc, ":Devices:1", &item),
+              PLIST_ERR_INVALID_ARG, "\"1\" doesn't exist",
+              "get one past the end");
+  check(item == NULL, "failed get clears the item");
+  check_error(plist_path_get_item(doc, ":Devices:5:UDID", &item),
+              PLIST_ERR_INVALID_ARG, "index 5 is past the end of an array of 1",
+              "get past the end");
+  check_error(plist_path_get_item(doc, ":Devices:x", &item),
+              PLIST_ERR_INVALID_ARG, "\"x\" isn't an array index",
+              "get a key of an array");
+  check_error(plist_path_get_item(doc, ":a\\:b:c", &item),
+              PLIST_ERR_INVALID_ARG, "isn't a dictionary or array",
+              "get inside an integer");
+
+  check(plist_path_set_item(doc, ":Devices:1:UDID", plist_new_string("def")) ==
+            PLIST_ERR_SUCCESS,
+        "set one past the end appends");
+  check(plist_path_set_item(doc, ":New:0:Deep", plist_new_bool(1)) ==
+            PLIST_ERR_SUCCESS,
+        "set creates the missing array and dictionaries");
+  check(plist_path_set_item(doc, ":Devices:0:UDID", plist_new_string("xyz")) ==
+            PLIST_ERR_SUCCESS,
+        "set replaces");
+  plist_t unused = plist_new_string("unused");
+  check_error(plist_path_set_item(doc, ":Devices:5", unused),
+              PLIST_ERR_INVALID_ARG, "index 5 is past the end of an array of 2",
+              "set past the end");
+  check(is_string(unused, "unused"), "failed set leaves the item alone");
+  plist_free(unused);
+  unused = plist_new_string("unused");
+  check_error(plist_path_set_item(doc, "", unused), PLIST_ERR_INVALID_ARG,
+              "can't replace the root", "set the root");
+  plist_free(unused);
+
+  check(plist_path_remove_item(doc, ":Devices:0") == PLIST_ERR_SUCCESS,
+        "remove an array element");
+  check(plist_path_remove_item(doc, "/a:b") == PLIST_ERR_SUCCESS,
+        "remove a key holding the other separator");
+  check_error(plist_path_remove_item(doc, ":Devices:1"), PLIST_ERR_INVALID_ARG,
+              "\"1\" doesn't exist", "remove past the end");
+  check_error(plist_path_remove_item(doc, ":missing"), PLIST_ERR_INVALID_ARG,
+              "\"missing\" doesn't exist", "remove a missing key");
+  check_error(plist_path_remove_item(doc, ""), PLIST_ERR_INVALID_ARG,
+              "can't remove the root", "remove the root");
+
+  plist_t expected = json("{\"Devices\":[{\"UDID\":\"def\"}],\"\":{\"\":7},"
+                          "\"New\":[{\"Deep\":true}]}");
+  check(plist_compare_node_value(doc, expected), "result is what was set");
+  plist_free(expected);
+
+  plist_free(doc);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
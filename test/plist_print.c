@@ -0,0 +1,27 @@
+/*
+ * plist_print.c
+ * Prints a plist with plist_print between two lines written with printf
+ */
+
+#include "../plist.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+
+int main(int argc, char *argv[]) {
+  if (argc < 2) {
+    fprintf(stderr, "Usage: %s <file.plist>\n", argv[0]);
+    return 2;
+  }
+
+  plist_t root = NULL;
+  if (plist_read_from_file(argv[1], &root, NULL) != PLIST_ERR_SUCCESS) {
+    fprintf(stderr, "Failed to read %s\n", argv[1]);
+    return 2;
+  }
+  printf("BEFORE\n");
+  plist_print(root);
+  printf("AFTER\n");
+  plist_free(root);
+  return 0;
+}
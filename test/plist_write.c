@@ -0,0 +1,65 @@
+/*
+ * plist_write.c
+ * Writes a plist to stdout with the given format and write options
+ */
+
+#include "../plist.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static const struct {
+  const char *name;
+  enum PlistFormat format;
+} formats[] = {
+    {"xml", PLIST_FORMAT_XML},     {"bin", PLIST_FORMAT_BINARY},
+    {"json", PLIST_FORMAT_JSON},   {"openstep", PLIST_FORMAT_OSTEP},
+    {"print", PLIST_FORMAT_PRINT}, {"limd", PLIST_FORMAT_LIMD},
+    {"plutil", PLIST_FORMAT_PLUTIL},
+};
+
+int main(int argc, char *argv[]) {
+  if (argc < 3) {
+    fprintf(stderr,
+            "Usage: %s <file.plist> <format> [compact] [no-newline] "
+            "[partial] [indent=N]\n",
+            argv[0]);
+    return 2;
+  }
+
+  enum PlistFormat format = PLIST_FORMAT_NONE;
+  for (size_t i = 0; i < sizeof(formats) / sizeof(formats[0]); i++) {
+    if (!strcmp(argv[2], formats[i].name)) {
+      format = formats[i].format;
+    }
+  }
+  PlistWriteOptions options = PLIST_OPT_NONE;
+  for (int i = 3; i < argc; i++) {
+    if (!strcmp(argv[i], "compact")) {
+      options |= PLIST_OPT_COMPACT;
+    } else if (!strcmp(argv[i], "no-newline")) {
+      options |= PLIST_OPT_NO_NEWLINE;
+    } else if (!strcmp(argv[i], "partial")) {
+      options |= PLIST_OPT_PARTIAL_DATA;
+    } else if (!strncmp(argv[i], "indent=", 7)) {
+      options |= PLIST_OPT_INDENT | (atoi(argv[i] + 7) << 24);
+    } else {
+      fprintf(stderr, "Unknown option %s\n", argv[i]);
+      return 2;
+    }
+  }
+
+  plist_t root = NULL;
+  if (plist_read_from_file(argv[1], &root, NULL) != PLIST_ERR_SUCCESS) {
+    fprintf(stderr, "Failed to read %s\n", argv[1]);
+    return 2;
+  }
+  plist_err_t res = plist_write_to_stream(root, stdout, format, options);
+  plist_free(root);
+  if (res != PLIST_ERR_SUCCESS) {
+    fprintf(stderr, "Failed to write: %s\n", plist_get_last_error());
+    return 1;
+  }
+  return 0;
+}
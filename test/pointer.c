@@ -0,0 +1,43 @@
+#include "test_helpers.h"
+
+static uint64_t uint_at(plist_t doc, const char *pointer) {
+  return uint_of(plist_access_pointer(doc, pointer));
+}
+
+/* Nothing was found and the last error says why */
+static void check_missing(plist_t doc, const char *pointer, const char *needle,
+                          const char *what) {
+  plist_clear_last_error();
+  plist_t node = plist_access_pointer(doc, pointer);
+  const char *last = plist_get_last_error();
+  check(!node && last && strstr(last, needle), what);
+}
+
+int main(int argc, char **argv) {
+  const char *text = "{\"a\":{\"b/c\":[10,20]},\"m~n\":1,\"~1\":3,"
+                     "\"\":{\"\":2},\"x\":{\"0\":4}}";
+  plist_t doc = NULL;
+  plist_from_json(text, strlen(text), &doc);
+
+  check(plist_access_pointer(doc, "") == doc, "empty pointer is the root");
+  check(uint_at(doc, "/a/b~1c/1") == 20, "~1 is a slash");
+  check(uint_at(doc, "/m~0n") == 1, "~0 is a tilde");
+  check(uint_at(doc, "/~01") == 3, "~01 is a tilde and a one, not a slash");
+  check(uint_at(doc, "//") == 2, "empty tokens are empty keys");
+  check(uint_at(doc, "/x/0") == 4, "a number is a key in a dictionary");
+
+  plist_t list = plist_dict_get_item(plist_dict_get_item(doc, "a"), "b/c");
+  check(plist_access_pointer(doc, "/a/b~1c/0") == plist_array_get_item(list, 0),
+        "hands out the same handle as the accessors");
+
+  check_missing(doc, "a", "isn't a JSON Pointer", "pointer without a slash");
+  check_missing(doc, "/missing", "\"missing\" doesn't exist", "missing key");
+  check_missing(doc, "/a/b~1c/2", "\"2\" doesn't exist", "one past the end");
+  check_missing(doc, "/a/b~1c/-", "\"-\" isn't an array index",
+                "- is past the end, there's nothing to find");
+  check_missing(doc, "/m~0n/0", "isn't a dictionary or array",
+                "inside an integer");
+
+  plist_free(doc);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,46 @@
+#include "test_helpers.h"
+
+int main(int argc, char **argv) {
+  plist_t root = json("{\"list\": [1, 2], \"name\": \"x\"}");
+
+  plist_t list = plist_dict_get_item(root, "list");
+  check(plist_dict_get_item(root, "list") == list,
+        "plist_dict_get_item hands out the same child twice");
+  plist_t first = plist_array_get_item(list, 0);
+  check(plist_array_get_item(list, 0) == first,
+        "plist_array_get_item hands out the same child twice");
+  check(plist_array_get_item(list, 1) != first,
+        "another index is another child");
+
+  int same = 1;
+  for (int i = 0; i < 10000; i++) {
+    same &= plist_dict_get_item(root, "list") == list;
+    same &= plist_array_get_item(list, 0) == first;
+  }
+  check(same, "polling the same element never makes a new child");
+
+  plist_dict_iter it = NULL;
+  char *key = NULL;
+  plist_t item = NULL;
+  plist_dict_new_iter(root, &it);
+  plist_dict_next_item(root, it, &key, &item);
+  check(item == list, "plist_dict_next_item reuses the child too");
+  free(key);
+  plist_dict_free_iter(it);
+
+  plist_array_iter ait = NULL;
+  plist_array_new_iter(list, &ait);
+  plist_array_next_item(list, ait, &item);
+  check(item == first, "plist_array_next_item reuses the child too");
+  plist_array_free_iter(ait);
+
+  plist_dict_remove_item(root, "name");
+  plist_dict_set_item(root, "name", plist_new_string("y"));
+  plist_t name = plist_dict_get_item(root, "name");
+  check(is_string(name, "y"), "a replaced key hands out its new value");
+  check(plist_dict_get_item(root, "name") == name,
+        "and keeps handing out that child");
+
+  plist_free(root);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
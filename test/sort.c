@@ -0,0 +1,73 @@
+#include "test_helpers.h"
+
+static const char *DOC =
+    "{\"z\":[{\"n\":3,\"id\":\"c\"},{\"n\":1,\"id\":\"a\"},{\"id\":\"none\"},"
+    "{\"n\":2,\"id\":\"b\"}],\"a\":{\"y\":1,\"b\":{\"d\":1,\"c\":2}}}";
+
+/* Compares the compact JSON, which keeps the order of keys */
+static void expect(plist_t node, const char *result, const char *what) {
+  char *out = NULL;
+  uint32_t size = 0;
+  plist_to_json(node, &out, &size, 0);
+  if (!out || strcmp(out, result) != 0) {
+    printf("ERROR: %s\n  got      %s\n  expected %s\n", what,
+           out ? out : "(null)", result);
+    err++;
+  } else {
+    printf("SUCCESS: %s\n", what);
+  }
+  free(out);
+}
+
+static int descending(plist_t a, plist_t b, void *user_data) {
+  uint64_t l = uint_of(a), r = uint_of(b);
+  (*(int *)user_data)++;
+  return (l < r) - (l > r);
+}
+
+int main(int argc, char **argv) {
+  plist_t doc = json(DOC);
+  plist_sort(doc);
+  expect(doc,
+         "{\"a\":{\"b\":{\"c\":2,\"d\":1},\"y\":1},\"z\":[{\"id\":\"c\",\"n\":3},"
+         "{\"id\":\"a\",\"n\":1},{\"id\":\"none\"},{\"id\":\"b\",\"n\":2}]}",
+         "plist_sort sorts nested keys and leaves arrays alone");
+  plist_free(doc);
+
+  doc = json(DOC);
+  plist_t z = plist_dict_get_item(doc, "z");
+  plist_t c = plist_array_get_item(z, 0);
+  plist_t none = plist_array_get_item(z, 2);
+  plist_t c_id = plist_dict_get_item(c, "id");
+  plist_sort_ex(doc, NULL, "n", NULL);
+  expect(doc,
+         "{\"a\":{\"b\":{\"c\":2,\"d\":1},\"y\":1},\"z\":[{\"id\":\"a\",\"n\":1},"
+         "{\"id\":\"b\",\"n\":2},{\"id\":\"c\",\"n\":3},{\"id\":\"none\"}]}",
+         "sort by key, elements without it go last");
+  check(plist_array_get_item_index(c) == 2 &&
+            plist_array_get_item(z, 2) == c &&
+            plist_array_get_item_index(none) == 3,
+        "handles follow their moved elements");
+  check(is_string(c_id, "c") &&
+            plist_dict_get_item(c, "id") == c_id,
+        "handles inside moved elements still resolve");
+
+  int calls = 0;
+  plist_sort_ex(z, descending, "n", &calls);
+  expect(z,
+         "[{\"id\":\"c\",\"n\":3},{\"id\":\"b\",\"n\":2},{\"id\":\"a\",\"n\":1},"
+         "{\"id\":\"none\"}]",
+         "comparator gets the values under the key");
+  check(calls > 0 && plist_array_get_item(z, 0) == c,
+        "handles follow the comparator's order");
+  plist_free(doc);
+
+  doc = json("[[3,1,2],{\"k\":[4,5]}]");
+  calls = 0;
+  plist_sort_ex(doc, descending, NULL, &calls);
+  expect(doc, "[[3,2,1],{\"k\":[5,4]}]",
+         "comparator alone sorts every array in the tree");
+  plist_free(doc);
+
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,69 @@
+#include <fcntl.h>
+#include <unistd.h>
+
+#include "test_helpers.h"
+
+int main(int argc, char **argv) {
+  const char *text = "{\"name\":\"libplist\",\"list\":[1,2.5,true]}";
+  plist_t root = NULL;
+  plist_from_json(text, strlen(text), &root);
+  plist_t parsed = NULL;
+  enum PlistFormat format = PLIST_FORMAT_NONE;
+
+  FILE *f = tmpfile();
+  check(plist_write_to_stream(root, f, PLIST_FORMAT_XML, 0) ==
+            PLIST_ERR_SUCCESS,
+        "write to a stream");
+  rewind(f);
+  check(plist_read_from_stream(f, &parsed, &format) == PLIST_ERR_SUCCESS &&
+            format == PLIST_FORMAT_XML &&
+            plist_compare_node_value(root, parsed),
+        "read it back from the stream");
+  fclose(f);
+  plist_free(parsed);
+
+  int fds[2];
+  if (pipe(fds) != 0) {
+    perror("pipe");
+    return EXIT_FAILURE;
+  }
+  check(plist_write_to_fd(root, fds[1], PLIST_FORMAT_BINARY, 0) ==
+            PLIST_ERR_SUCCESS,
+        "write to a pipe");
+  close(fds[1]);
+  parsed = NULL;
+  check(plist_read_from_fd(fds[0], &parsed, &format) == PLIST_ERR_SUCCESS &&
+            format == PLIST_FORMAT_BINARY &&
+            plist_compare_node_value(root, parsed),
+        "read it back from the pipe");
+  close(fds[0]);
+  plist_free(parsed);
+
+  /* a descriptor that was just closed */
+  int closed = open("/dev/null", O_WRONLY);
+  close(closed);
+  check_error(plist_write_to_fd(root, closed, PLIST_FORMAT_JSON, 0),
+              PLIST_ERR_IO, "write failed", "writing to a closed fd");
+  parsed = NULL;
+  check_error(plist_read_from_fd(closed, &parsed, &format), PLIST_ERR_IO,
+              "read failed", "reading from a closed fd");
+  check(parsed == NULL, "failed read hands out nothing");
+
+  /* the device is always full */
+  f = fopen("/dev/full", "w");
+  if (f) {
+    setvbuf(f, NULL, _IONBF, 0);
+    check_error(plist_write_to_stream(root, f, PLIST_FORMAT_PRINT, 0),
+                PLIST_ERR_IO, "write failed", "writing to a full stream");
+    fclose(f);
+    check_error(plist_write_to_file(root, "/dev/full", PLIST_FORMAT_XML, 0),
+                PLIST_ERR_IO, "write failed", "writing to a full file");
+  }
+  f = fopen("/dev/null", "r");
+  check_error(plist_write_to_stream(root, f, PLIST_FORMAT_JSON, 0),
+              PLIST_ERR_IO, "write failed", "writing to a read only stream");
+  fclose(f);
+
+  plist_free(root);
+  return (err > 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
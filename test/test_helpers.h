@@ -0,0 +1,49 @@
+/* Checks shared by the C test programs, each one counts its failures in err */
+#ifndef PLIST_TEST_HELPERS_H
+#define PLIST_TEST_HELPERS_H
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "../plist.h"
+
+static int err = 0;
+
+static inline void check(int ok, const char *what) {
+  if (ok) {
+    printf("SUCCESS: %s\n", what);
+  } else {
+    printf("ERROR: %s\n", what);
+    err++;
+  }
+}
+
+/* The call failed with the expected code and the last error says why */
+static inline void check_error(plist_err_t res, plist_err_t expected,
+                               const char *needle, const char *what) {
+  const char *last = plist_get_last_error();
+  check(res == expected && last && strstr(last, needle), what);
+}
+
+static inline plist_t json(const char *text) {
+  plist_t node = NULL;
+  plist_from_json(text, strlen(text), &node);
+  return node;
+}
+
+static inline uint64_t uint_of(plist_t node) {
+  uint64_t val = UINT64_MAX;
+  plist_get_uint_val(node, &val);
+  return val;
+}
+
+/* string_ptr isn't null terminated, only the length is to be trusted */
+static inline int is_string(plist_t node, const char *expected) {
+  uint64_t length = 0;
+  const char *val = plist_get_string_ptr(node, &length);
+  return val && length == strlen(expected) && !memcmp(val, expected, length);
+}
+
+#endif
@@ -31,18 +31,13 @@
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
-#include <sys/stat.h>
-#ifndef _MSC_VER
-#include <unistd.h>
-#endif
 
 #ifdef _MSC_VER
 #pragma warning(disable : 4996)
-#define STDIN_FILENO _fileno(stdin)
 #endif
 
 typedef struct _options {
-  char *in_file, *out_file;
+  char *in_file, *out_file, *diff_file;
   uint8_t in_fmt,
       out_fmt; // fmts 0 = undef, 1 = bin, 2 = xml, 3 = json, 4 = openstep
   uint8_t flags;
@@ -77,6 +72,9 @@ static void print_usage(int argc, char *argv[]) {
       "                       If omitted, XML will be converted to binary,\n");
   printf("                       and binary to XML.\n");
   printf("  -p, --print FILE     Print the PList in human-readable format.\n");
+  printf("  -D, --diff FILE      Output the changes from the input to FILE as "
+         "a plist\n");
+  printf("                       array, XML unless -f is given.\n");
   printf("  -c, --compact        JSON and OpenStep only: Print output in "
          "compact form.\n");
   printf("                       By default, the output will be "
@@ -154,6 +152,14 @@ static options_t *parse_arguments(int argc, char *argv[]) {
       }
       i++;
       continue;
+    } else if (!strcmp(argv[i], "--diff") || !strcmp(argv[i], "-D")) {
+      if ((i + 1) == argc) {
+        free(options);
+        return NULL;
+      }
+      options->diff_file = argv[i + 1];
+      i++;
+      continue;
     } else if (!strcmp(argv[i], "--debug") || !strcmp(argv[i], "-d")) {
       options->flags |= OPT_DEBUG;
     } else if (!strcmp(argv[i], "--help") || !strcmp(argv[i], "-h")) {
@@ -169,6 +175,11 @@ static options_t *parse_arguments(int argc, char *argv[]) {
     }
   }
 
+  // A diff is rarely wanted as binary
+  if (options->diff_file && options->out_fmt == 0) {
+    options->out_fmt = PLIST_FORMAT_XML;
+  }
+
   return options;
 }
 
@@ -180,10 +191,7 @@ int main(int argc, char *argv[]) {
   plist_t root_node = NULL;
   char *plist_out = NULL;
   uint32_t size = 0;
-  int read_size = 0;
-  int read_capacity = 4096;
-  char *plist_entire = NULL;
-  struct stat filestats;
+  PlistFormat in_fmt = PLIST_FORMAT_NONE;
   options_t *options = parse_arguments(argc, argv);
 
   if (!options) {
@@ -196,49 +204,7 @@ int main(int argc, char *argv[]) {
   }
 
   if (!options->in_file || !strcmp(options->in_file, "-")) {
-    read_size = 0;
-    plist_entire = malloc(sizeof(char) * read_capacity);
-    if (plist_entire == NULL) {
-      fprintf(stderr, "ERROR: Failed to allocate buffer to read from stdin");
-      free(options);
-      return 1;
-    }
-    plist_entire[read_size] = '\0';
-    char ch;
-    while (read(STDIN_FILENO, &ch, 1) > 0) {
-      if (read_size >= read_capacity) {
-        char *old = plist_entire;
-        read_capacity += 4096;
-        plist_entire = realloc(plist_entire, sizeof(char) * read_capacity);
-        if (plist_entire == NULL) {
-          fprintf(stderr, "ERROR: Failed to reallocate stdin buffer\n");
-          free(old);
-          free(options);
-          return 1;
-        }
-      }
-      plist_entire[read_size] = ch;
-      read_size++;
-    }
-    if (read_size >= read_capacity) {
-      char *old = plist_entire;
-      plist_entire = realloc(plist_entire, sizeof(char) * (read_capacity + 1));
-      if (plist_entire == NULL) {
-        fprintf(stderr, "ERROR: Failed to reallocate stdin buffer\n");
-        free(old);
-        free(options);
-        return 1;
-      }
-    }
-    plist_entire[read_size] = '\0';
-
-    // Not positive we need this, but it doesnt seem to hurt lol
-    if (ferror(stdin)) {
-      fprintf(stderr, "ERROR: reading from stdin.\n");
-      free(plist_entire);
-      free(options);
-      return 1;
-    }
+    input_res = plist_read_from_stream(stdin, &root_node, &in_fmt);
   } else {
     // read input file
     iplist = fopen(options->in_file, "rb");
@@ -248,37 +214,37 @@ int main(int argc, char *argv[]) {
       free(options);
       return 1;
     }
-
-    memset(&filestats, '\0', sizeof(struct stat));
-    fstat(fileno(iplist), &filestats);
-
-    plist_entire = (char *)malloc(sizeof(char) * (filestats.st_size + 1));
-    read_size = fread(plist_entire, sizeof(char), filestats.st_size, iplist);
-    plist_entire[read_size] = '\0';
+    input_res = plist_read_from_stream(iplist, &root_node, &in_fmt);
     fclose(iplist);
   }
 
-  if (options->out_fmt == 0) {
+  if (input_res == PLIST_ERR_SUCCESS && options->out_fmt == 0) {
     // convert from binary to xml or vice-versa
-    if (plist_is_binary(plist_entire, read_size)) {
-      input_res = plist_from_bin(plist_entire, read_size, &root_node);
-      if (input_res == PLIST_ERR_SUCCESS) {
-        if (options->flags & OPT_SORT) {
-          plist_sort(root_node);
-        }
-        output_res = plist_to_xml(root_node, &plist_out, &size);
+    if (in_fmt == PLIST_FORMAT_BINARY) {
+      if (options->flags & OPT_SORT) {
+        plist_sort(root_node);
       }
+      output_res = plist_to_xml(root_node, &plist_out, &size);
+    } else if (in_fmt == PLIST_FORMAT_XML) {
+      if (options->flags & OPT_SORT) {
+        plist_sort(root_node);
+      }
+      output_res = plist_to_bin(root_node, &plist_out, &size);
     } else {
-      input_res = plist_from_xml(plist_entire, read_size, &root_node);
+      input_res = PLIST_ERR_PARSE;
+    }
+  } else if (input_res == PLIST_ERR_SUCCESS) {
+    if (options->diff_file) {
+      plist_t other = NULL;
+      plist_t changes = NULL;
+      input_res = plist_read_from_file(options->diff_file, &other, NULL);
       if (input_res == PLIST_ERR_SUCCESS) {
-        if (options->flags & OPT_SORT) {
-          plist_sort(root_node);
-        }
-        output_res = plist_to_bin(root_node, &plist_out, &size);
+        input_res = plist_diff(root_node, other, &changes);
       }
+      plist_free(other);
+      plist_free(root_node);
+      root_node = changes;
     }
-  } else {
-    input_res = plist_from_memory(plist_entire, read_size, &root_node, NULL);
     if (input_res == PLIST_ERR_SUCCESS) {
       if (options->flags & OPT_SORT) {
         plist_sort(root_node);
@@ -297,14 +263,12 @@ int main(int argc, char *argv[]) {
         plist_write_to_stream(root_node, stdout, options->out_fmt,
                               PLIST_OPT_PARTIAL_DATA);
         plist_free(root_node);
-        free(plist_entire);
         free(options);
         return 0;
       }
     }
   }
   plist_free(root_node);
-  free(plist_entire);
 
   if (plist_out) {
     if (options->out_file != NULL && strcmp(options->out_file, "-") != 0) {
@@ -355,6 +319,9 @@ int main(int argc, char *argv[]) {
       ret = 1;
       break;
     }
+    if (plist_get_last_error()) {
+      fprintf(stderr, "%s\n", plist_get_last_error());
+    }
   }
 
   free(options);